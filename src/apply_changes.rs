@@ -33,12 +33,34 @@ pub fn apply_change(
             event.location = room;
         }
 
+        match change.tentative {
+            Some(true) if event.status == EventStatus::Confirmed => {
+                event.status = EventStatus::Tentative;
+            }
+            Some(false) if event.status == EventStatus::Tentative => {
+                event.status = EventStatus::Confirmed;
+            }
+            _ => {}
+        }
+        if let Some(transparent) = change.transparent {
+            event.transparent = transparent;
+        }
+
+        let all_day = event.all_day;
         if let Some(time) = change.starttime {
             event.start_time = date.date().and_time(time);
+            event.all_day = false;
         }
 
         if let Some(time) = change.endtime {
-            event.end_time = date.date().and_time(time);
+            // Multi day events keep their last day, all day events end on their first day
+            let end_date = if all_day {
+                date.date()
+            } else {
+                event.end_time.date()
+            };
+            event.end_time = end_date.and_time(time);
+            event.all_day = false;
         }
     } else {
         // Event for this change doesnt exist.
@@ -51,7 +73,6 @@ fn generate_events() -> Vec<SoonToBeIcsEvent> {
     vec![
         SoonToBeIcsEvent {
             name: "BTI5-VSP/01".to_owned(),
-            status: EventStatus::Confirmed,
             start_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
                 .unwrap()
                 .and_hms_opt(8, 15, 0)
@@ -60,13 +81,10 @@ fn generate_events() -> Vec<SoonToBeIcsEvent> {
                 .unwrap()
                 .and_hms_opt(11, 15, 0)
                 .unwrap(),
-            alert_minutes_before: None,
-            description: String::new(),
-            location: String::new(),
+            ..SoonToBeIcsEvent::default()
         },
         SoonToBeIcsEvent {
             name: "BTI5-VSP/01".to_owned(),
-            status: EventStatus::Confirmed,
             start_time: chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
                 .unwrap()
                 .and_hms_opt(8, 15, 0)
@@ -75,9 +93,7 @@ fn generate_events() -> Vec<SoonToBeIcsEvent> {
                 .unwrap()
                 .and_hms_opt(11, 15, 0)
                 .unwrap(),
-            alert_minutes_before: None,
-            description: String::new(),
-            location: String::new(),
+            ..SoonToBeIcsEvent::default()
         },
     ]
 }
@@ -95,6 +111,8 @@ fn non_existing_event_of_change_is_skipped() {
         endtime: None,
         namesuffix: None,
         room: None,
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Cancelled);
     assert_eq!(events.len(), 2);
@@ -117,6 +135,8 @@ fn remove_event_is_removed_completely() {
         endtime: None,
         namesuffix: None,
         room: None,
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Removed);
     assert_eq!(events.len(), 1);
@@ -135,6 +155,8 @@ fn remove_event_gets_marked_as_cancelled() {
         endtime: None,
         namesuffix: None,
         room: None,
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Cancelled);
     assert_eq!(events.len(), 2);
@@ -154,6 +176,8 @@ fn remove_event_gets_emoji_prefix() {
         endtime: None,
         namesuffix: None,
         room: None,
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Emoji);
     assert_eq!(events.len(), 2);
@@ -173,6 +197,8 @@ fn namesuffix_is_added() {
         endtime: None,
         namesuffix: Some("whatever".to_owned()),
         room: None,
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Cancelled);
     assert_eq!(events[1].name, "BTI5-VSP/01 whatever");
@@ -191,6 +217,8 @@ fn room_is_overwritten() {
         endtime: None,
         namesuffix: None,
         room: Some("wherever".to_owned()),
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Cancelled);
     assert_eq!(events[1].location, "wherever");
@@ -209,6 +237,8 @@ fn starttime_changed() {
         endtime: None,
         namesuffix: None,
         room: None,
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Cancelled);
     assert_eq!(
@@ -233,6 +263,8 @@ fn endtime_changed() {
         endtime: Some(chrono::NaiveTime::from_hms_opt(8, 30, 0).unwrap()),
         namesuffix: None,
         room: None,
+        tentative: None,
+        transparent: None,
     };
     apply_change(&mut events, date, change, RemovedEvents::Cancelled);
     assert_eq!(
@@ -243,3 +275,104 @@ fn endtime_changed() {
             .unwrap()
     );
 }
+
+#[test]
+fn time_change_of_all_day_event_makes_it_timed() {
+    let mut events = generate_events();
+    let date = chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    events[1].start_time = date;
+    events[1].end_time = date + chrono::Days::new(3);
+    events[1].all_day = true;
+    let change = Change {
+        remove: false,
+        starttime: Some(chrono::NaiveTime::from_hms_opt(9, 0, 0).unwrap()),
+        endtime: Some(chrono::NaiveTime::from_hms_opt(12, 0, 0).unwrap()),
+        namesuffix: None,
+        room: None,
+        tentative: None,
+        transparent: None,
+    };
+    apply_change(&mut events, date, change, RemovedEvents::Cancelled);
+    assert!(!events[1].all_day);
+    assert_eq!(
+        events[1].end_time,
+        chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
+            .unwrap()
+            .and_hms_opt(12, 0, 0)
+            .unwrap()
+    );
+}
+
+#[test]
+fn endtime_change_of_multi_day_event_keeps_last_day() {
+    let mut events = generate_events();
+    let date = chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap();
+    events[1].end_time = events[1].end_time + chrono::Days::new(2);
+    let change = Change {
+        remove: false,
+        starttime: None,
+        endtime: Some(chrono::NaiveTime::from_hms_opt(16, 0, 0).unwrap()),
+        namesuffix: None,
+        room: None,
+        tentative: None,
+        transparent: None,
+    };
+    apply_change(&mut events, date, change, RemovedEvents::Cancelled);
+    assert_eq!(
+        events[1].end_time,
+        chrono::NaiveDate::from_ymd_opt(2020, 5, 16)
+            .unwrap()
+            .and_hms_opt(16, 0, 0)
+            .unwrap()
+    );
+}
+
+#[test]
+fn instance_overrides_tentative_and_transparent() {
+    let mut events = generate_events();
+    events[0].status = EventStatus::Tentative;
+    events[0].transparent = true;
+    let date = chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap();
+    let change = Change {
+        remove: false,
+        starttime: None,
+        endtime: None,
+        namesuffix: None,
+        room: None,
+        tentative: Some(false),
+        transparent: Some(false),
+    };
+    apply_change(&mut events, date, change, RemovedEvents::Cancelled);
+    assert_eq!(events[0].status, EventStatus::Confirmed);
+    assert!(!events[0].transparent);
+}
+
+#[test]
+fn cancelled_event_stays_cancelled_when_tentative() {
+    let mut events = generate_events();
+    let date = chrono::NaiveDate::from_ymd_opt(2020, 5, 14)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap();
+    let change = Change {
+        remove: true,
+        starttime: None,
+        endtime: None,
+        namesuffix: None,
+        room: None,
+        tentative: Some(true),
+        transparent: Some(true),
+    };
+    apply_change(&mut events, date, change, RemovedEvents::Cancelled);
+    assert_eq!(events[1].status, EventStatus::Cancelled);
+    assert!(events[1].transparent);
+}
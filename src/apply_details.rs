@@ -1,9 +1,14 @@
-use crate::generate_ics::SoonToBeIcsEvent;
+use crate::generate_ics::{EventStatus, SoonToBeIcsEvent};
 use crate::userconfig::EventDetails;
 
 pub fn apply_details(event: &mut SoonToBeIcsEvent, details: &EventDetails) {
     event.alert_minutes_before = details.alert_minutes_before;
 
+    if details.tentative && event.status == EventStatus::Confirmed {
+        event.status = EventStatus::Tentative;
+    }
+    event.transparent = details.transparent;
+
     if let Some(notes) = &details.notes
         && !notes.is_empty()
     {
@@ -19,7 +24,6 @@ pub fn apply_details(event: &mut SoonToBeIcsEvent, details: &EventDetails) {
 fn create_event(description: &str) -> SoonToBeIcsEvent {
     SoonToBeIcsEvent {
         name: "BTI5-VSP/01".to_owned(),
-        status: crate::generate_ics::EventStatus::Confirmed,
         start_time: chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
             .unwrap()
             .and_hms_opt(8, 15, 0)
@@ -28,9 +32,8 @@ fn create_event(description: &str) -> SoonToBeIcsEvent {
             .unwrap()
             .and_hms_opt(11, 15, 0)
             .unwrap(),
-        alert_minutes_before: None,
         description: description.to_owned(),
-        location: String::new(),
+        ..SoonToBeIcsEvent::default()
     }
 }
 
@@ -83,3 +86,21 @@ fn some_note_no_description() {
 fn some_note_some_description() {
     check_description(Some("foo"), "bar", "bar\n\nfoo");
 }
+
+#[test]
+fn subscription_can_be_tentative_and_transparent() {
+    let details = EventDetails {
+        tentative: true,
+        transparent: true,
+        ..EventDetails::default()
+    };
+    let mut event = create_event("");
+    apply_details(&mut event, &details);
+    assert_eq!(event.status, EventStatus::Tentative);
+    assert!(event.transparent);
+
+    let mut event = create_event("");
+    apply_details(&mut event, &EventDetails::default());
+    assert_eq!(event.status, EventStatus::Confirmed);
+    assert!(!event.transparent);
+}
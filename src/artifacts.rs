@@ -0,0 +1,160 @@
+//! Files written next to each calendar so static servers can deliver them without recomputing anything.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::path::Path;
+use std::time::SystemTime;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sha2::{Digest as _, Sha256};
+
+use crate::generate_ics::SoonToBeIcsEvent;
+
+pub const ICS: &str = ".ics";
+pub const GZIP: &str = ".ics.gz";
+pub const BROTLI: &str = ".ics.br";
+pub const METADATA: &str = ".ics.meta.json";
+pub const JCAL: &str = ".json";
+pub const XCAL: &str = ".xcs";
+pub const HTML: &str = ".html";
+/// Overlapping events for the bot to show
+pub const CONFLICTS: &str = ".conflicts.json";
+
+/// Every extension belonging to a calendar. Longer ones first so they are stripped completely.
+pub const EXTENSIONS: &[&str] = &[METADATA, CONFLICTS, GZIP, BROTLI, ICS, JCAL, XCAL, HTML];
+
+/// Strip the artifact extension from the filename: `1337-secret.ics.gz` → `1337-secret`
+pub fn stem(filename: &str) -> Option<&str> {
+    EXTENSIONS
+        .iter()
+        .find_map(|extension| filename.strip_suffix(extension))
+}
+
+/// `CALENDARS_BROTLI` enables the additional brotli artifact
+pub fn brotli_enabled() -> bool {
+    env_flag("CALENDARS_BROTLI")
+}
+
+/// `CALENDARS_JCAL` enables the additional jCal (RFC 7265) artifact
+pub fn jcal_enabled() -> bool {
+    env_flag("CALENDARS_JCAL")
+}
+
+/// `CALENDARS_XCAL` enables the additional xCal (RFC 6321) artifact
+pub fn xcal_enabled() -> bool {
+    env_flag("CALENDARS_XCAL")
+}
+
+/// `CALENDARS_HTML` enables the additional printable weekly timetable
+pub fn html_enabled() -> bool {
+    env_flag("CALENDARS_HTML")
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| matches!(value.as_str(), "1" | "true"))
+}
+
+/// Metadata sidecar of a calendar
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Metadata {
+    /// Hex encoded SHA-256 of the ICS content. Usable as strong `ETag`.
+    pub sha256: String,
+    pub event_count: usize,
+    pub first_event: Option<NaiveDateTime>,
+    pub last_event: Option<NaiveDateTime>,
+    pub generated: DateTime<Utc>,
+}
+
+impl Metadata {
+    pub fn new(content: &str, events: &[SoonToBeIcsEvent]) -> Self {
+        Self {
+            sha256: sha256_hex(content.as_bytes()),
+            event_count: events.len(),
+            first_event: events.iter().map(|event| event.start_time).min(),
+            last_event: events.iter().map(|event| event.end_time).max(),
+            generated: SystemTime::now().into(),
+        }
+    }
+
+    pub fn read(path: &Path) -> Option<Self> {
+        let content = fs::read_to_string(path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+}
+
+pub fn sha256_hex(content: &[u8]) -> String {
+    let mut result = String::with_capacity(64);
+    for byte in Sha256::digest(content) {
+        _ = write!(result, "{byte:02x}");
+    }
+    result
+}
+
+pub fn gzip(content: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    encoder
+        .write_all(content)
+        .expect("writing into memory should work");
+    encoder.finish().expect("writing into memory should work")
+}
+
+pub fn brotli(content: &[u8]) -> Vec<u8> {
+    let mut result = Vec::new();
+    {
+        let mut encoder = brotli::CompressorWriter::new(&mut result, 4096, 11, 22);
+        encoder
+            .write_all(content)
+            .expect("writing into memory should work");
+    }
+    result
+}
+
+#[test]
+fn stem_examples() {
+    assert_eq!(stem("1337-secret.ics"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.ics.gz"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.ics.br"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.ics.meta.json"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.json"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.xcs"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.html"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.conflicts.json"), Some("1337-secret"));
+    assert_eq!(stem("1337-secret.txt"), None);
+}
+
+#[test]
+fn sha256_example() {
+    assert_eq!(
+        sha256_hex(b"abc"),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
+
+#[test]
+fn metadata_of_events() {
+    let event = |day: u32| SoonToBeIcsEvent {
+        name: "BTI5-VS".to_owned(),
+        start_time: chrono::NaiveDate::from_ymd_opt(2020, 8, day)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap(),
+        end_time: chrono::NaiveDate::from_ymd_opt(2020, 8, day)
+            .unwrap()
+            .and_hms_opt(11, 30, 0)
+            .unwrap(),
+        ..SoonToBeIcsEvent::default()
+    };
+    let events = [event(22), event(29)];
+
+    let metadata = Metadata::new("content", &events);
+    assert_eq!(metadata.event_count, 2);
+    assert_eq!(metadata.first_event, Some(events[0].start_time));
+    assert_eq!(metadata.last_event, Some(events[1].end_time));
+
+    let json = serde_json::to_string(&metadata).unwrap();
+    assert!(json.contains(r#""eventCount":2"#), "{json}");
+    let parsed: Metadata = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, metadata);
+}
@@ -0,0 +1,82 @@
+//! The eventfiles and calendars use the local time of Hamburg (Europe/Berlin) without timezone information.
+
+use std::time::SystemTime;
+
+use chrono::{DateTime, Datelike as _, Duration, NaiveDate, NaiveDateTime, Utc, Weekday};
+
+/// Current local time in Europe/Berlin
+pub fn now() -> NaiveDateTime {
+    let utc: DateTime<Utc> = SystemTime::now().into();
+    from_utc(utc.naive_utc())
+}
+
+/// Central European (Summer) Time: daylight saving time between the last Sundays of March and October at 01:00 UTC
+pub fn to_utc(local: NaiveDateTime) -> NaiveDateTime {
+    let standard = local - Duration::hours(1);
+    if is_daylight_saving_time(standard) {
+        local - Duration::hours(2)
+    } else {
+        standard
+    }
+}
+
+pub fn from_utc(utc: NaiveDateTime) -> NaiveDateTime {
+    if is_daylight_saving_time(utc) {
+        utc + Duration::hours(2)
+    } else {
+        utc + Duration::hours(1)
+    }
+}
+
+fn is_daylight_saving_time(utc: NaiveDateTime) -> bool {
+    let year = utc.year();
+    let dst_start = last_sunday(year, 3)
+        .and_hms_opt(1, 0, 0)
+        .unwrap_or_default();
+    let dst_end = last_sunday(year, 10)
+        .and_hms_opt(1, 0, 0)
+        .unwrap_or_default();
+    utc >= dst_start && utc < dst_end
+}
+
+fn last_sunday(year: i32, month: u32) -> NaiveDate {
+    let first_of_next = NaiveDate::from_ymd_opt(year, month + 1, 1).unwrap_or_default();
+    let last = first_of_next.pred_opt().unwrap_or_default();
+    let days_since_sunday = last.weekday().days_since(Weekday::Sun);
+    last - Duration::days(days_since_sunday.into())
+}
+
+#[test]
+fn to_utc_examples() {
+    let winter = NaiveDate::from_ymd_opt(2022, 1, 13)
+        .unwrap()
+        .and_hms_opt(11, 40, 0)
+        .unwrap();
+    assert_eq!(to_utc(winter), winter - Duration::hours(1));
+    let summer = NaiveDate::from_ymd_opt(2022, 7, 14)
+        .unwrap()
+        .and_hms_opt(8, 0, 0)
+        .unwrap();
+    assert_eq!(to_utc(summer), summer - Duration::hours(2));
+    let before_switch = NaiveDate::from_ymd_opt(2022, 3, 27)
+        .unwrap()
+        .and_hms_opt(1, 30, 0)
+        .unwrap();
+    assert_eq!(to_utc(before_switch), before_switch - Duration::hours(1));
+    let after_switch = NaiveDate::from_ymd_opt(2022, 3, 27)
+        .unwrap()
+        .and_hms_opt(3, 30, 0)
+        .unwrap();
+    assert_eq!(to_utc(after_switch), after_switch - Duration::hours(2));
+}
+
+#[test]
+fn from_utc_is_inverse() {
+    for (month, day, hour) in [(1, 13, 10), (3, 27, 0), (3, 27, 1), (7, 14, 6), (10, 30, 2)] {
+        let utc = NaiveDate::from_ymd_opt(2022, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 30, 0)
+            .unwrap();
+        assert_eq!(to_utc(from_utc(utc)), utc, "{utc}");
+    }
+}
@@ -0,0 +1,107 @@
+//! Anonymized variant of the calendar of a user to share the availability: `calendars/busy/<chat>-<suffix>.ics`.
+
+use crate::generate_ics::{
+    EventMeta, EventStatus, SoonToBeIcsEvent, calculate_event_hash, generate_ics,
+};
+
+/// Subfolder of the calendars folder
+pub const SUBFOLDER: &str = "busy";
+
+/// Name of the calendar and summary of every event instead of the name of the user or module
+const BUSY: &str = "Belegt";
+
+/// Busy calendar of the events without anything identifying the user
+pub fn generate_busy_ics(events: &[SoonToBeIcsEvent]) -> String {
+    generate_ics(BUSY, &busy_events(events))
+}
+
+/// Only the times and the transparency of the events. Cancelled events are left out.
+fn busy_events(events: &[SoonToBeIcsEvent]) -> Vec<SoonToBeIcsEvent> {
+    let mut result: Vec<SoonToBeIcsEvent> = Vec::new();
+    for event in events
+        .iter()
+        .filter(|event| event.status != EventStatus::Cancelled)
+    {
+        let busy = SoonToBeIcsEvent {
+            name: BUSY.to_owned(),
+            status: event.status,
+            start_time: event.start_time,
+            end_time: event.end_time,
+            alert_minutes_before: None,
+            description: String::new(),
+            location: String::new(),
+            lecturers: Vec::new(),
+            all_day: event.all_day,
+            transparent: event.transparent,
+            meta: EventMeta::default(),
+        };
+        // Parallel events would end up with the same UID
+        let uid = calculate_event_hash(&busy);
+        if !result
+            .iter()
+            .any(|existing| calculate_event_hash(existing) == uid)
+        {
+            result.push(busy);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+fn create_event(name: &str, hour: u32) -> SoonToBeIcsEvent {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+        .unwrap()
+        .and_hms_opt(hour, 15, 0)
+        .unwrap();
+    SoonToBeIcsEvent {
+        name: name.to_owned(),
+        start_time,
+        end_time: start_time + chrono::Duration::hours(3),
+        alert_minutes_before: Some(15),
+        description: "Dozent: HTM\n\nnotes of Peter".to_owned(),
+        location: "1060".to_owned(),
+        lecturers: vec!["HTM".to_owned()],
+        meta: EventMeta {
+            url: Some("https://example.com/BTI5-VS".to_owned()),
+            original_id: Some("42".to_owned()),
+            ..EventMeta::default()
+        },
+        ..SoonToBeIcsEvent::default()
+    }
+}
+
+#[test]
+fn busy_events_are_anonymized() {
+    let mut cancelled = create_event("BTI5-VS", 14);
+    cancelled.status = EventStatus::Cancelled;
+    let mut tutorial = create_event("BTI5-VS Tutorium", 18);
+    tutorial.status = EventStatus::Tentative;
+    tutorial.transparent = true;
+    let events = [
+        create_event("BTI5-VS", 8),
+        create_event("BTI5-VSP/01", 8),
+        cancelled,
+        tutorial,
+    ];
+
+    let busy = busy_events(&events);
+    assert_eq!(busy.len(), 2);
+    assert!(busy.iter().all(|event| event.name == BUSY
+        && event.location.is_empty()
+        && event.description.is_empty()
+        && event.alert_minutes_before.is_none()
+        && event.lecturers.is_empty()
+        && event.meta == EventMeta::default()));
+    assert_eq!(busy[0].start_time, events[0].start_time);
+    assert_eq!(busy[1].status, EventStatus::Tentative);
+    assert!(busy[1].transparent);
+
+    let ics = generate_busy_ics(&events);
+    assert!(
+        ics.contains("X-WR-CALNAME:@HAWHHCalendarBot (Belegt)"),
+        "{ics}"
+    );
+    assert!(!ics.contains("Peter"), "{ics}");
+    assert!(!ics.contains("BTI5"), "{ics}");
+    assert!(!ics.contains(&calculate_event_hash(&events[0])), "{ics}");
+}
@@ -0,0 +1,1148 @@
+//! Read-only `CalDAV` access to the generated calendars.
+//!
+//! Every calendar `<chat id>-<suffix>.ics` is exposed below `/caldav/<chat id>-<suffix>/`:
+//! the principal and calendar home at that path, the calendar collection at `calendar/` and
+//! the events of every UID as one resource `calendar/<uid>.ics`.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use quick_xml::NsReader;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::name::ResolveResult;
+
+use crate::artifacts::sha256_hex;
+use crate::berlin_time;
+use crate::http_server::{IncomingRequest, NOT_FOUND, Reply, is_calendar_filename};
+
+pub const PREFIX: &str = "/caldav/";
+
+const NS_DAV: &str = "DAV:";
+const NS_CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+const NS_CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+const SYNC_TOKEN_PREFIX: &str = "https://calendarbot.hawhh.de/ns/sync/";
+const ALLOW: &str = "OPTIONS, GET, HEAD, PROPFIND, REPORT";
+
+/// Remember this many past states per calendar to answer sync-collection reports with changes only
+const SYNC_HISTORY_LENGTH: usize = 50;
+
+/// Past states of the calendars identified by their sync token.
+#[derive(Debug, Default)]
+pub struct SyncHistory {
+    snapshots: Mutex<HashMap<String, VecDeque<Snapshot>>>,
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot {
+    token: String,
+    /// href to etag
+    etags: BTreeMap<String, String>,
+}
+
+impl SyncHistory {
+    fn remember(&self, name: &str, snapshot: &Snapshot) {
+        let mut snapshots = self
+            .snapshots
+            .lock()
+            .expect("sync history should not be poisoned");
+        let history = snapshots.entry(name.to_owned()).or_default();
+        if history
+            .back()
+            .is_none_or(|last| last.token != snapshot.token)
+        {
+            history.push_back(snapshot.clone());
+            if history.len() > SYNC_HISTORY_LENGTH {
+                history.pop_front();
+            }
+        }
+        drop(snapshots);
+    }
+
+    fn find(&self, name: &str, token: &str) -> Option<Snapshot> {
+        let snapshots = self
+            .snapshots
+            .lock()
+            .expect("sync history should not be poisoned");
+        snapshots
+            .get(name)?
+            .iter()
+            .find(|snapshot| snapshot.token == token)
+            .cloned()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PropName {
+    namespace: String,
+    local: String,
+}
+
+impl PropName {
+    fn new(namespace: &str, local: &str) -> Self {
+        Self {
+            namespace: namespace.to_owned(),
+            local: local.to_owned(),
+        }
+    }
+
+    fn is(&self, namespace: &str, local: &str) -> bool {
+        self.namespace == namespace && self.local == local
+    }
+
+    fn to_empty_element(&self) -> String {
+        namespace_prefix(&self.namespace).map_or_else(
+            || {
+                format!(
+                    "<x:{} xmlns:x=\"{}\"/>",
+                    self.local,
+                    escape(&self.namespace)
+                )
+            },
+            |prefix| format!("<{prefix}:{}/>", self.local),
+        )
+    }
+}
+
+fn namespace_prefix(namespace: &str) -> Option<&'static str> {
+    match namespace {
+        NS_DAV => Some("d"),
+        NS_CALDAV => Some("c"),
+        NS_CALENDARSERVER => Some("cs"),
+        _ => None,
+    }
+}
+
+/// The parts of a `WebDAV` request body this read-only server cares about
+#[derive(Debug, Default)]
+struct DavRequest {
+    /// Local name of the root element like `propfind` or `sync-collection`
+    root: String,
+    /// Requested properties. Empty means all properties.
+    props: Vec<PropName>,
+    hrefs: Vec<String>,
+    sync_token: Option<String>,
+    /// UTC range of a calendar-query time-range filter
+    time_range: Option<(Option<NaiveDateTime>, Option<NaiveDateTime>)>,
+}
+
+fn parse_dav_request(body: &str) -> Option<DavRequest> {
+    let mut request = DavRequest::default();
+    if body.trim().is_empty() {
+        // An empty PROPFIND body means allprop
+        return Some(request);
+    }
+
+    let mut reader = NsReader::from_str(body);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<PropName> = Vec::new();
+    let mut text = String::new();
+
+    loop {
+        let (resolved, event) = reader.read_resolved_event().ok()?;
+        let namespace = match resolved {
+            ResolveResult::Bound(namespace) => {
+                String::from_utf8_lossy(namespace.as_ref()).into_owned()
+            }
+            _ => String::new(),
+        };
+        match event {
+            Event::Start(element) => {
+                let name = start_element(&mut request, &stack, namespace, &element)?;
+                stack.push(name);
+                text.clear();
+            }
+            Event::Empty(element) => {
+                start_element(&mut request, &stack, namespace, &element)?;
+            }
+            Event::Text(content) => text += &content.decode().ok()?,
+            Event::GeneralRef(reference) => match reference.decode().ok()?.as_ref() {
+                "amp" => text.push('&'),
+                "lt" => text.push('<'),
+                "gt" => text.push('>'),
+                "quot" => text.push('"'),
+                "apos" => text.push('\''),
+                _ => {}
+            },
+            Event::End(_) => {
+                let name = stack.pop()?;
+                if name.is(NS_DAV, "href") {
+                    request.hrefs.push(text.trim().to_owned());
+                } else if name.is(NS_DAV, "sync-token") {
+                    request.sync_token = Some(text.trim().to_owned());
+                }
+                text.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Some(request)
+}
+
+fn start_element(
+    request: &mut DavRequest,
+    stack: &[PropName],
+    namespace: String,
+    element: &BytesStart,
+) -> Option<PropName> {
+    let name = PropName {
+        namespace,
+        local: String::from_utf8_lossy(element.local_name().as_ref()).into_owned(),
+    };
+    if stack.is_empty() {
+        request.root.clone_from(&name.local);
+    }
+    if stack.last().is_some_and(|parent| parent.is(NS_DAV, "prop")) {
+        request.props.push(name.clone());
+    }
+    if name.is(NS_CALDAV, "time-range") {
+        let mut start = None;
+        let mut end = None;
+        for attribute in element.attributes().flatten() {
+            let value = attribute.unescape_value().ok()?;
+            let value = NaiveDateTime::parse_from_str(&value, "%Y%m%dT%H%M%SZ").ok();
+            match attribute.key.local_name().as_ref() {
+                b"start" => start = value,
+                b"end" => end = value,
+                _ => {}
+            }
+        }
+        request.time_range = Some((start, end));
+    }
+    Some(name)
+}
+
+#[derive(Debug)]
+struct Calendar {
+    name: String,
+    displayname: String,
+    events: Vec<EventResource>,
+}
+
+#[derive(Debug)]
+struct EventResource {
+    /// Filename of the resource within the calendar collection
+    filename: String,
+    etag: String,
+    data: String,
+    /// UTC times for time-range filters
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+}
+
+impl Calendar {
+    fn load(folder: &Path, name: &str) -> Option<Self> {
+        if !is_calendar_filename(&format!("{name}.ics")) {
+            return None;
+        }
+        let content = fs::read_to_string(folder.join(format!("{name}.ics"))).ok()?;
+        Some(Self::parse(name, &content))
+    }
+
+    /// Split the generated ICS into standalone calendars containing the VEVENTs of one UID each
+    fn parse(name: &str, content: &str) -> Self {
+        // Calendar object resources must not contain METHOD (RFC 4791 section 4.1)
+        let mut header = String::new();
+        let mut displayname = None;
+        let mut timezones = String::new();
+        let mut vevents: Vec<String> = Vec::new();
+        let mut current: Option<String> = None;
+        let mut in_timezone = false;
+
+        for line in content.lines() {
+            if let Some(vevent) = &mut current {
+                *vevent += line;
+                *vevent += "\r\n";
+                if line == "END:VEVENT" {
+                    vevents.push(current.take().unwrap_or_default());
+                }
+            } else if line == "BEGIN:VEVENT" {
+                current = Some(format!("{line}\r\n"));
+            } else if line == "BEGIN:VTIMEZONE" || in_timezone {
+                in_timezone = line != "END:VTIMEZONE";
+                timezones += line;
+                timezones += "\r\n";
+            } else if line == "BEGIN:VCALENDAR"
+                || line.starts_with("VERSION:")
+                || line.starts_with("PRODID:")
+            {
+                header += line;
+                header += "\r\n";
+            } else if let Some(calname) = line.strip_prefix("X-WR-CALNAME:") {
+                displayname = Some(calname.to_owned());
+            }
+        }
+        let displayname = displayname.unwrap_or_else(|| name.to_owned());
+
+        // A UID is exactly one resource (RFC 4791 section 4.1). Identical events share their UID.
+        let mut by_uid: Vec<(String, Vec<String>)> = Vec::new();
+        for vevent in vevents {
+            let uid = property_value(&vevent, "UID").unwrap_or_default();
+            match by_uid.iter_mut().find(|(existing, _)| *existing == uid) {
+                Some((_, group)) if group.contains(&vevent) => {}
+                Some((_, group)) => group.push(vevent),
+                None => by_uid.push((uid, vec![vevent])),
+            }
+        }
+
+        let events = by_uid
+            .into_iter()
+            .map(|(uid, vevents)| {
+                let base = uid.split('@').next().unwrap_or_default();
+                let base = if base.is_empty() { "event" } else { base };
+                let start = vevents
+                    .iter()
+                    .filter_map(|vevent| property_value(vevent, "DTSTART"))
+                    .filter_map(|value| parse_ics_datetime(&value))
+                    .min();
+                let end = vevents
+                    .iter()
+                    .filter_map(|vevent| property_value(vevent, "DTEND"))
+                    .filter_map(|value| parse_ics_datetime(&value))
+                    .max();
+                let data = format!("{header}{timezones}{}END:VCALENDAR\r\n", vevents.concat());
+                let etag = format!("\"{}\"", sha256_hex(data.as_bytes()));
+                EventResource {
+                    filename: format!("{base}.ics"),
+                    etag,
+                    data,
+                    start,
+                    end,
+                }
+            })
+            .collect();
+
+        Self {
+            name: name.to_owned(),
+            displayname,
+            events,
+        }
+    }
+
+    fn home_href(&self) -> String {
+        format!("{PREFIX}{}/", self.name)
+    }
+
+    fn collection_href(&self) -> String {
+        format!("{PREFIX}{}/calendar/", self.name)
+    }
+
+    fn event_href(&self, event: &EventResource) -> String {
+        format!("{}{}", self.collection_href(), event.filename)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let etags = self
+            .events
+            .iter()
+            .map(|event| (self.event_href(event), event.etag.clone()))
+            .collect::<BTreeMap<_, _>>();
+        let mut state = String::new();
+        for (href, etag) in &etags {
+            _ = writeln!(state, "{href} {etag}");
+        }
+        Snapshot {
+            token: format!("{SYNC_TOKEN_PREFIX}{}", sha256_hex(state.as_bytes())),
+            etags,
+        }
+    }
+}
+
+fn property_value(vevent: &str, property: &str) -> Option<String> {
+    vevent.lines().find_map(|line| {
+        let rest = line.strip_prefix(property)?;
+        if !rest.starts_with([':', ';']) {
+            return None;
+        }
+        rest.split_once(':').map(|(_, value)| value.to_owned())
+    })
+}
+
+/// Parse the local Europe/Berlin date times of the generated calendars into UTC.
+/// Dates of all day events start at local midnight.
+fn parse_ics_datetime(value: &str) -> Option<NaiveDateTime> {
+    if let Some(utc) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok();
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Some(berlin_time::to_utc(date.and_time(NaiveTime::MIN)));
+    }
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some(berlin_time::to_utc(local))
+}
+
+#[derive(Debug)]
+enum Target {
+    Home,
+    Collection,
+    Event(String),
+}
+
+fn parse_path(path: &str) -> Option<(&str, Target)> {
+    let rest = path.strip_prefix(PREFIX)?;
+    let (name, rest) = rest.split_once('/').unwrap_or((rest, ""));
+    let target = match rest.trim_end_matches('/') {
+        "" => Target::Home,
+        "calendar" => Target::Collection,
+        other => Target::Event(other.strip_prefix("calendar/")?.to_owned()),
+    };
+    Some((name, target))
+}
+
+pub fn respond(folder: &Path, history: &SyncHistory, request: &IncomingRequest) -> Reply {
+    let path = request.url.split(['?', '#']).next().unwrap_or_default();
+    let Some((name, target)) = parse_path(path) else {
+        return Reply::plain(404, NOT_FOUND);
+    };
+    let Some(calendar) = Calendar::load(folder, name) else {
+        return Reply::plain(404, NOT_FOUND);
+    };
+    let snapshot = calendar.snapshot();
+    history.remember(name, &snapshot);
+
+    if let Target::Event(filename) = &target
+        && !calendar
+            .events
+            .iter()
+            .any(|event| &event.filename == filename)
+    {
+        return Reply::plain(404, NOT_FOUND);
+    }
+
+    match request.method.as_str() {
+        "OPTIONS" => Reply {
+            status: 200,
+            headers: vec![
+                ("DAV", "1, 3, calendar-access".to_owned()),
+                ("Allow", ALLOW.to_owned()),
+            ],
+            body: Vec::new(),
+        },
+        "GET" | "HEAD" => get(&calendar, &target, request),
+        "PROPFIND" => {
+            let Some(dav_request) = parse_dav_request(&request.body) else {
+                return Reply::plain(400, "Bad Request");
+            };
+            let depth_one = request.depth.as_deref().is_some_and(|depth| depth != "0");
+            propfind(&calendar, &snapshot, &target, &dav_request, depth_one)
+        }
+        "REPORT" => {
+            let Some(dav_request) = parse_dav_request(&request.body) else {
+                return Reply::plain(400, "Bad Request");
+            };
+            if !matches!(target, Target::Collection) {
+                return Reply::plain(403, "Forbidden");
+            }
+            report(&calendar, &snapshot, history, &dav_request)
+        }
+        _ => {
+            let mut reply = Reply::plain(405, "Method Not Allowed");
+            reply.headers.push(("Allow", ALLOW.to_owned()));
+            reply
+        }
+    }
+}
+
+fn get(calendar: &Calendar, target: &Target, request: &IncomingRequest) -> Reply {
+    let Target::Event(filename) = target else {
+        return Reply::plain(404, NOT_FOUND);
+    };
+    let Some(event) = calendar
+        .events
+        .iter()
+        .find(|event| &event.filename == filename)
+    else {
+        return Reply::plain(404, NOT_FOUND);
+    };
+    let headers = vec![("ETag", event.etag.clone())];
+    let not_modified = request
+        .if_none_match
+        .as_deref()
+        .is_some_and(|if_none_match| {
+            if_none_match
+                .split(',')
+                .any(|candidate| candidate.trim() == "*" || candidate.trim() == event.etag)
+        });
+    if not_modified {
+        return Reply {
+            status: 304,
+            headers,
+            body: Vec::new(),
+        };
+    }
+    let mut headers = headers;
+    headers.push((
+        "Content-Type",
+        "text/calendar; charset=utf-8; component=vevent".to_owned(),
+    ));
+    Reply {
+        status: 200,
+        headers,
+        body: event.data.clone().into_bytes(),
+    }
+}
+
+fn propfind(
+    calendar: &Calendar,
+    snapshot: &Snapshot,
+    target: &Target,
+    request: &DavRequest,
+    depth_one: bool,
+) -> Reply {
+    let mut body = String::new();
+    match target {
+        Target::Home => {
+            write_response(
+                &mut body,
+                &calendar.home_href(),
+                &Resource::Home(calendar),
+                &request.props,
+            );
+            if depth_one {
+                write_response(
+                    &mut body,
+                    &calendar.collection_href(),
+                    &Resource::Collection(calendar, snapshot),
+                    &request.props,
+                );
+            }
+        }
+        Target::Collection => {
+            write_response(
+                &mut body,
+                &calendar.collection_href(),
+                &Resource::Collection(calendar, snapshot),
+                &request.props,
+            );
+            if depth_one {
+                for event in &calendar.events {
+                    write_response(
+                        &mut body,
+                        &calendar.event_href(event),
+                        &Resource::Event(calendar, event),
+                        &request.props,
+                    );
+                }
+            }
+        }
+        Target::Event(filename) => {
+            if let Some(event) = calendar
+                .events
+                .iter()
+                .find(|event| &event.filename == filename)
+            {
+                write_response(
+                    &mut body,
+                    &calendar.event_href(event),
+                    &Resource::Event(calendar, event),
+                    &request.props,
+                );
+            }
+        }
+    }
+    multistatus(&body)
+}
+
+fn report(
+    calendar: &Calendar,
+    snapshot: &Snapshot,
+    history: &SyncHistory,
+    request: &DavRequest,
+) -> Reply {
+    let mut body = String::new();
+    match request.root.as_str() {
+        "calendar-query" => {
+            for event in &calendar.events {
+                if is_in_time_range(event, request.time_range) {
+                    write_response(
+                        &mut body,
+                        &calendar.event_href(event),
+                        &Resource::Event(calendar, event),
+                        &request.props,
+                    );
+                }
+            }
+        }
+        "calendar-multiget" => {
+            for href in &request.hrefs {
+                let event = calendar
+                    .events
+                    .iter()
+                    .find(|event| href_matches(href, &calendar.event_href(event)));
+                match event {
+                    Some(event) => write_response(
+                        &mut body,
+                        href,
+                        &Resource::Event(calendar, event),
+                        &request.props,
+                    ),
+                    None => write_status_response(&mut body, href, "404 Not Found"),
+                }
+            }
+        }
+        "sync-collection" => {
+            let since = match request
+                .sync_token
+                .as_deref()
+                .filter(|token| !token.is_empty())
+            {
+                None => None,
+                Some(token) => match history.find(&calendar.name, token) {
+                    Some(snapshot) => Some(snapshot),
+                    None => {
+                        return Reply {
+                            status: 403,
+                            headers: vec![(
+                                "Content-Type",
+                                "application/xml; charset=utf-8".to_owned(),
+                            )],
+                            body: r#"<?xml version="1.0" encoding="utf-8"?>
+<d:error xmlns:d="DAV:"><d:valid-sync-token/></d:error>
+"#
+                            .as_bytes()
+                            .to_vec(),
+                        };
+                    }
+                },
+            };
+            for event in &calendar.events {
+                let href = calendar.event_href(event);
+                let unchanged = since
+                    .as_ref()
+                    .is_some_and(|since| since.etags.get(&href) == Some(&event.etag));
+                if !unchanged {
+                    write_response(
+                        &mut body,
+                        &href,
+                        &Resource::Event(calendar, event),
+                        &request.props,
+                    );
+                }
+            }
+            if let Some(since) = since {
+                for href in since.etags.keys() {
+                    if !snapshot.etags.contains_key(href) {
+                        write_status_response(&mut body, href, "404 Not Found");
+                    }
+                }
+            }
+            _ = writeln!(
+                body,
+                "<d:sync-token>{}</d:sync-token>",
+                escape(&snapshot.token)
+            );
+        }
+        _ => return Reply::plain(403, "Forbidden"),
+    }
+    multistatus(&body)
+}
+
+/// Clients might send absolute URLs or percent-encoded paths
+fn href_matches(requested: &str, href: &str) -> bool {
+    let requested = requested
+        .split_once("://")
+        .and_then(|(_, rest)| rest.find('/').map(|index| &rest[index..]))
+        .unwrap_or(requested);
+    requested == href || requested.replace("%40", "@") == href
+}
+
+fn is_in_time_range(
+    event: &EventResource,
+    time_range: Option<(Option<NaiveDateTime>, Option<NaiveDateTime>)>,
+) -> bool {
+    let Some((range_start, range_end)) = time_range else {
+        return true;
+    };
+    let (Some(start), Some(end)) = (event.start, event.end) else {
+        return true;
+    };
+    range_start.is_none_or(|range_start| end > range_start)
+        && range_end.is_none_or(|range_end| start < range_end)
+}
+
+enum Resource<'a> {
+    Home(&'a Calendar),
+    Collection(&'a Calendar, &'a Snapshot),
+    Event(&'a Calendar, &'a EventResource),
+}
+
+impl Resource<'_> {
+    fn default_props(&self) -> Vec<PropName> {
+        let mut props = vec![
+            PropName::new(NS_DAV, "resourcetype"),
+            PropName::new(NS_DAV, "displayname"),
+        ];
+        match self {
+            Self::Home(_) => {
+                props.push(PropName::new(NS_DAV, "current-user-principal"));
+                props.push(PropName::new(NS_CALDAV, "calendar-home-set"));
+            }
+            Self::Collection(..) => {
+                props.push(PropName::new(NS_DAV, "sync-token"));
+                props.push(PropName::new(NS_CALENDARSERVER, "getctag"));
+                props.push(PropName::new(NS_CALDAV, "supported-calendar-component-set"));
+            }
+            Self::Event(..) => {
+                props.push(PropName::new(NS_DAV, "getetag"));
+                props.push(PropName::new(NS_DAV, "getcontenttype"));
+            }
+        }
+        props
+    }
+
+    /// Render the property with its value or `None` when the resource does not have it
+    fn render(&self, prop: &PropName) -> Option<String> {
+        let calendar = match self {
+            Self::Home(calendar) | Self::Collection(calendar, _) | Self::Event(calendar, _) => {
+                calendar
+            }
+        };
+        let home_href = || format!("<d:href>{}</d:href>", escape(calendar.home_href()));
+        let value = match (prop.namespace.as_str(), prop.local.as_str(), self) {
+            (NS_DAV, "resourcetype", Self::Home(_)) => "<d:collection/><d:principal/>".to_owned(),
+            (NS_DAV, "resourcetype", Self::Collection(..)) => {
+                "<d:collection/><c:calendar/>".to_owned()
+            }
+            (NS_DAV, "resourcetype", Self::Event(..)) => String::new(),
+            (NS_DAV, "displayname", Self::Home(_) | Self::Collection(..)) => {
+                escape(&calendar.displayname).into_owned()
+            }
+            (NS_DAV, "current-user-principal" | "principal-URL" | "owner", _)
+            | (NS_CALDAV, "calendar-home-set", _) => home_href(),
+            (NS_DAV, "current-user-privilege-set", _) => {
+                "<d:privilege><d:read/></d:privilege>".to_owned()
+            }
+            (NS_DAV, "supported-report-set", Self::Collection(..)) => [
+                "c:calendar-query",
+                "c:calendar-multiget",
+                "d:sync-collection",
+            ]
+            .map(|report| {
+                format!("<d:supported-report><d:report><{report}/></d:report></d:supported-report>")
+            })
+            .concat(),
+            (NS_DAV, "sync-token", Self::Collection(_, snapshot))
+            | (NS_CALENDARSERVER, "getctag", Self::Collection(_, snapshot)) => {
+                escape(&snapshot.token).into_owned()
+            }
+            (NS_CALDAV, "supported-calendar-component-set", Self::Collection(..)) => {
+                r#"<c:comp name="VEVENT"/>"#.to_owned()
+            }
+            (NS_DAV, "getetag", Self::Event(_, event)) => escape(&event.etag).into_owned(),
+            (NS_DAV, "getcontenttype", Self::Event(..)) => {
+                "text/calendar; charset=utf-8; component=vevent".to_owned()
+            }
+            (NS_CALDAV, "calendar-data", Self::Event(_, event)) => escape(&event.data).into_owned(),
+            _ => return None,
+        };
+        let prefix = namespace_prefix(&prop.namespace)?;
+        Some(format!(
+            "<{prefix}:{local}>{value}</{prefix}:{local}>",
+            local = prop.local
+        ))
+    }
+}
+
+fn write_response(output: &mut String, href: &str, resource: &Resource, requested: &[PropName]) {
+    let defaults;
+    let requested = if requested.is_empty() {
+        defaults = resource.default_props();
+        &defaults
+    } else {
+        requested
+    };
+
+    let mut found = String::new();
+    let mut missing = String::new();
+    for prop in requested {
+        match resource.render(prop) {
+            Some(rendered) => found += &rendered,
+            None => missing += &prop.to_empty_element(),
+        }
+    }
+
+    _ = write!(output, "<d:response><d:href>{}</d:href>", escape(href));
+    if !found.is_empty() {
+        _ = write!(
+            output,
+            "<d:propstat><d:prop>{found}</d:prop><d:status>HTTP/1.1 200 OK</d:status></d:propstat>"
+        );
+    }
+    if !missing.is_empty() {
+        _ = write!(
+            output,
+            "<d:propstat><d:prop>{missing}</d:prop><d:status>HTTP/1.1 404 Not Found</d:status></d:propstat>"
+        );
+    }
+    _ = writeln!(output, "</d:response>");
+}
+
+fn write_status_response(output: &mut String, href: &str, status: &str) {
+    _ = writeln!(
+        output,
+        "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 {status}</d:status></d:response>",
+        escape(href)
+    );
+}
+
+fn multistatus(responses: &str) -> Reply {
+    let body = format!(
+        r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="{NS_DAV}" xmlns:c="{NS_CALDAV}" xmlns:cs="{NS_CALENDARSERVER}">
+{responses}</d:multistatus>
+"#
+    );
+    Reply {
+        status: 207,
+        headers: vec![("Content-Type", "application/xml; charset=utf-8".to_owned())],
+        body: body.into_bytes(),
+    }
+}
+
+#[cfg(test)]
+const TEST_CALENDAR: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nMETHOD:PUBLISH\r\nPRODID:https://calendarbot.hawhh.de\r\nX-WR-CALNAME:@HAWHHCalendarBot (Peter)\r\nBEGIN:VTIMEZONE\r\nTZID:Europe/Berlin\r\nEND:VTIMEZONE\r\nBEGIN:VEVENT\r\nSUMMARY:BTI1-TI\r\nDTSTART;TZID=Europe/Berlin:20220113T114000\r\nDTEND;TZID=Europe/Berlin:20220113T120000\r\nUID:aaa@calendarbot.hawhh.de\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nSUMMARY:BTI5-VS\r\nDTSTART;TZID=Europe/Berlin:20220714T080000\r\nDTEND;TZID=Europe/Berlin:20220714T093000\r\nUID:bbb@calendarbot.hawhh.de\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+
+#[cfg(test)]
+fn create_folder() -> tempfile::TempDir {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::write(tempdir.path().join("1337-secret.ics"), TEST_CALENDAR).unwrap();
+    tempdir
+}
+
+#[cfg(test)]
+fn request(method: &str, url: &str, depth: Option<&str>, body: &str) -> IncomingRequest {
+    IncomingRequest {
+        method: method.to_owned(),
+        url: url.to_owned(),
+        depth: depth.map(ToOwned::to_owned),
+        body: body.to_owned(),
+        ..IncomingRequest::default()
+    }
+}
+
+#[cfg(test)]
+fn body_of(reply: &Reply) -> String {
+    String::from_utf8(reply.body.clone()).unwrap()
+}
+
+#[test]
+fn splits_calendar_into_event_resources() {
+    let calendar = Calendar::parse("1337-secret", TEST_CALENDAR);
+    assert_eq!(calendar.displayname, "@HAWHHCalendarBot (Peter)");
+    assert_eq!(calendar.events.len(), 2);
+
+    let event = &calendar.events[0];
+    assert_eq!(event.filename, "aaa.ics");
+    assert!(event.data.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(
+        event
+            .data
+            .contains("TZID:Europe/Berlin\r\nEND:VTIMEZONE\r\nBEGIN:VEVENT\r\nSUMMARY:BTI1-TI\r\n")
+    );
+    assert!(!event.data.contains("BTI5-VS"));
+    assert!(event.data.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+    assert_ne!(event.etag, calendar.events[1].etag);
+}
+
+#[test]
+fn event_resources_have_no_method() {
+    let calendar = Calendar::parse("1337-secret", TEST_CALENDAR);
+    for event in &calendar.events {
+        assert!(!event.data.contains("METHOD:"), "{}", event.data);
+        assert!(!event.data.contains("X-WR-CALNAME:"), "{}", event.data);
+        assert!(event.data.contains("\r\nPRODID:"), "{}", event.data);
+    }
+}
+
+#[test]
+fn changed_event_is_deleted_and_added() {
+    let start_time = NaiveDate::from_ymd_opt(2022, 1, 13)
+        .unwrap()
+        .and_hms_opt(11, 40, 0)
+        .unwrap();
+    let event = |name: &str, day: u64| crate::generate_ics::SoonToBeIcsEvent {
+        name: name.to_owned(),
+        start_time: start_time + chrono::Days::new(day),
+        end_time: start_time + chrono::Days::new(day) + chrono::Duration::minutes(20),
+        ..crate::generate_ics::SoonToBeIcsEvent::default()
+    };
+    let before =
+        crate::generate_ics::generate_ics("Peter", &[event("BTI1-TI", 0), event("BTI5-VS", 1)]);
+    let after =
+        crate::generate_ics::generate_ics("Peter", &[event("BTI1-TI", 0), event("BTI5-VSP", 1)]);
+    let first = Calendar::parse("1337-secret", &before);
+    let second = Calendar::parse("1337-secret", &after);
+
+    // Unchanged events keep their resource and etag
+    assert_eq!(first.events[0].filename, second.events[0].filename);
+    assert_eq!(first.events[0].etag, second.events[0].etag);
+
+    // The UID is built from the content, a changed event is another resource
+    let filenames = |calendar: &Calendar| {
+        calendar
+            .events
+            .iter()
+            .map(|event| event.filename.clone())
+            .collect::<Vec<_>>()
+    };
+    assert!(!filenames(&second).contains(&first.events[1].filename));
+    assert!(!filenames(&first).contains(&second.events[1].filename));
+}
+
+#[test]
+fn identical_events_are_one_resource() {
+    let start_time = NaiveDate::from_ymd_opt(2022, 1, 13)
+        .unwrap()
+        .and_hms_opt(11, 40, 0)
+        .unwrap();
+    let event = |description: &str| crate::generate_ics::SoonToBeIcsEvent {
+        name: "BTI1-TI".to_owned(),
+        start_time,
+        end_time: start_time + chrono::Duration::minutes(20),
+        description: description.to_owned(),
+        ..crate::generate_ics::SoonToBeIcsEvent::default()
+    };
+    let mut other_lecturer = event("");
+    other_lecturer.lecturers = vec!["HTM".to_owned()];
+    let ics = crate::generate_ics::generate_ics("Peter", &[event(""), event(""), other_lecturer]);
+
+    let calendar = Calendar::parse("1337-secret", &ics);
+    assert_eq!(calendar.events.len(), 1);
+    let data = &calendar.events[0].data;
+    assert_eq!(data.matches("BEGIN:VEVENT").count(), 2, "{data}");
+    assert!(data.contains("CONTACT:HTM"), "{data}");
+}
+
+#[test]
+fn all_day_events_have_their_time_range() {
+    let event = crate::generate_ics::SoonToBeIcsEvent {
+        name: "Prüfungszeitraum".to_owned(),
+        start_time: NaiveDate::from_ymd_opt(2022, 2, 1)
+            .unwrap()
+            .and_time(NaiveTime::MIN),
+        end_time: NaiveDate::from_ymd_opt(2022, 2, 5)
+            .unwrap()
+            .and_time(NaiveTime::MIN),
+        all_day: true,
+        ..crate::generate_ics::SoonToBeIcsEvent::default()
+    };
+    let ics = crate::generate_ics::generate_ics("Peter", &[event]);
+    let calendar = Calendar::parse("1337-secret", &ics);
+    let resource = &calendar.events[0];
+    let utc = |month: u32, day: u32, hour: u32| {
+        NaiveDate::from_ymd_opt(2022, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+    };
+    // Local midnight of the first day until local midnight after the last day
+    assert_eq!(resource.start, utc(1, 31, 23));
+    assert_eq!(resource.end, utc(2, 4, 23));
+
+    assert!(is_in_time_range(
+        resource,
+        Some((utc(2, 3, 0), utc(2, 10, 0)))
+    ));
+    assert!(!is_in_time_range(
+        resource,
+        Some((utc(2, 5, 0), utc(2, 10, 0)))
+    ));
+    assert!(!is_in_time_range(
+        resource,
+        Some((utc(1, 1, 0), utc(1, 31, 0)))
+    ));
+}
+
+#[test]
+fn unknown_calendars_are_not_found() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    for url in [
+        "/caldav/",
+        "/caldav/1338-secret/",
+        "/caldav/1337-secret/calendar/ccc.ics",
+        "/caldav/../1337-secret/",
+    ] {
+        let reply = respond(
+            folder.path(),
+            &history,
+            &request("PROPFIND", url, Some("0"), ""),
+        );
+        assert_eq!(reply.status, 404, "{url}");
+        assert_eq!(body_of(&reply), NOT_FOUND, "{url}");
+    }
+}
+
+#[test]
+fn writing_is_not_allowed() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    let reply = respond(
+        folder.path(),
+        &history,
+        &request(
+            "PUT",
+            "/caldav/1337-secret/calendar/aaa.ics",
+            None,
+            "BEGIN:VCALENDAR",
+        ),
+    );
+    assert_eq!(reply.status, 405);
+}
+
+#[test]
+fn propfind_home_finds_calendar() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    let body = r#"<?xml version="1.0"?><d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><d:current-user-principal/><c:calendar-home-set/><d:resourcetype/></d:prop></d:propfind>"#;
+    let reply = respond(
+        folder.path(),
+        &history,
+        &request("PROPFIND", "/caldav/1337-secret/", Some("1"), body),
+    );
+    assert_eq!(reply.status, 207);
+    let body = body_of(&reply);
+    assert!(body.contains("<d:current-user-principal><d:href>/caldav/1337-secret/</d:href></d:current-user-principal>"), "{body}");
+    assert!(
+        body.contains("<d:href>/caldav/1337-secret/calendar/</d:href>"),
+        "{body}"
+    );
+    assert!(
+        body.contains("<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>"),
+        "{body}"
+    );
+}
+
+#[test]
+fn propfind_collection_lists_events() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    let body = r#"<propfind xmlns="DAV:" xmlns:x="http://example.com/ns/"><prop><getetag/><x:color/></prop></propfind>"#;
+    let reply = respond(
+        folder.path(),
+        &history,
+        &request("PROPFIND", "/caldav/1337-secret/calendar/", Some("1"), body),
+    );
+    let body = body_of(&reply);
+    assert!(
+        body.contains("<d:href>/caldav/1337-secret/calendar/aaa.ics</d:href>"),
+        "{body}"
+    );
+    assert!(
+        body.contains("<d:href>/caldav/1337-secret/calendar/bbb.ics</d:href>"),
+        "{body}"
+    );
+    assert!(body.contains("<d:getetag>&quot;"), "{body}");
+    assert!(
+        body.contains(r#"<x:color xmlns:x="http://example.com/ns/"/>"#),
+        "{body}"
+    );
+    assert!(body.contains("404 Not Found"), "{body}");
+}
+
+#[test]
+fn get_single_event() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    let reply = respond(
+        folder.path(),
+        &history,
+        &request("GET", "/caldav/1337-secret/calendar/bbb.ics", None, ""),
+    );
+    assert_eq!(reply.status, 200);
+    let body = body_of(&reply);
+    assert!(body.contains("SUMMARY:BTI5-VS"));
+    assert!(!body.contains("SUMMARY:BTI1-TI"));
+}
+
+#[test]
+fn calendar_query_filters_time_range() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    let body = r#"<c:calendar-query xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><d:getetag/><c:calendar-data/></d:prop><c:filter><c:comp-filter name="VCALENDAR"><c:comp-filter name="VEVENT"><c:time-range start="20220601T000000Z" end="20220801T000000Z"/></c:comp-filter></c:comp-filter></c:filter></c:calendar-query>"#;
+    let reply = respond(
+        folder.path(),
+        &history,
+        &request("REPORT", "/caldav/1337-secret/calendar/", Some("1"), body),
+    );
+    assert_eq!(reply.status, 207);
+    let body = body_of(&reply);
+    assert!(!body.contains("aaa.ics"), "{body}");
+    assert!(body.contains("bbb.ics"), "{body}");
+    assert!(body.contains("<c:calendar-data>BEGIN:VCALENDAR"), "{body}");
+}
+
+#[test]
+fn calendar_multiget_returns_requested() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    let body = r#"<c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"><d:prop><d:getetag/></d:prop><d:href>/caldav/1337-secret/calendar/aaa.ics</d:href><d:href>/caldav/1337-secret/calendar/ccc.ics</d:href></c:calendar-multiget>"#;
+    let reply = respond(
+        folder.path(),
+        &history,
+        &request("REPORT", "/caldav/1337-secret/calendar/", None, body),
+    );
+    let body = body_of(&reply);
+    assert!(
+        body.contains("<d:href>/caldav/1337-secret/calendar/aaa.ics</d:href><d:propstat>"),
+        "{body}"
+    );
+    assert!(body.contains("<d:href>/caldav/1337-secret/calendar/ccc.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status>"), "{body}");
+    assert!(!body.contains("bbb.ics"), "{body}");
+}
+
+#[test]
+fn sync_collection_reports_changes_since_token() {
+    let folder = create_folder();
+    let history = SyncHistory::default();
+    let sync = |token: &str| {
+        let body = format!(
+            r#"<d:sync-collection xmlns:d="DAV:"><d:sync-token>{token}</d:sync-token><d:sync-level>1</d:sync-level><d:prop><d:getetag/></d:prop></d:sync-collection>"#
+        );
+        respond(
+            folder.path(),
+            &history,
+            &request("REPORT", "/caldav/1337-secret/calendar/", None, &body),
+        )
+    };
+
+    let initial = body_of(&sync(""));
+    assert!(
+        initial.contains("aaa.ics") && initial.contains("bbb.ics"),
+        "{initial}"
+    );
+    let token = initial
+        .split("<d:sync-token>")
+        .nth(1)
+        .and_then(|rest| rest.split('<').next())
+        .unwrap()
+        .to_owned();
+
+    let unchanged = body_of(&sync(&token));
+    assert!(
+        !unchanged.contains("aaa.ics") && !unchanged.contains("bbb.ics"),
+        "{unchanged}"
+    );
+
+    let changed = TEST_CALENDAR.replace(
+        "BEGIN:VEVENT\r\nSUMMARY:BTI1-TI\r\nDTSTART;TZID=Europe/Berlin:20220113T114000\r\nDTEND;TZID=Europe/Berlin:20220113T120000\r\nUID:aaa@calendarbot.hawhh.de\r\nEND:VEVENT\r\n",
+        "",
+    ).replace("SUMMARY:BTI5-VS", "SUMMARY:BTI5-VSP");
+    fs::write(folder.path().join("1337-secret.ics"), changed).unwrap();
+
+    let delta = body_of(&sync(&token));
+    assert!(delta.contains("<d:href>/caldav/1337-secret/calendar/aaa.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status>"), "{delta}");
+    assert!(
+        delta.contains("<d:href>/caldav/1337-secret/calendar/bbb.ics</d:href><d:propstat>"),
+        "{delta}"
+    );
+
+    let unknown = sync("https://calendarbot.hawhh.de/ns/sync/unknown");
+    assert_eq!(unknown.status, 403);
+    assert!(body_of(&unknown).contains("valid-sync-token"));
+}
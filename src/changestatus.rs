@@ -2,8 +2,12 @@ use std::collections::HashMap;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Changetype {
+    /// Superfluous calendar kept because removing it would exceed the cleanup limit
+    Aborted,
     Added,
     Changed,
+    /// The userconfig or calendar could not be built. The existing calendar is kept.
+    Failed,
     Moved,
     Removed,
     Same,
@@ -12,20 +16,30 @@ pub enum Changetype {
 
 impl Changetype {
     pub const ALL: &'static [Self] = &[
+        Self::Aborted,
         Self::Added,
         Self::Changed,
+        Self::Failed,
         Self::Moved,
         Self::Removed,
         Self::Same,
         Self::Skipped,
     ];
-    pub const INTERESTING: &'static [Self] =
-        &[Self::Added, Self::Changed, Self::Moved, Self::Removed];
+    pub const INTERESTING: &'static [Self] = &[
+        Self::Aborted,
+        Self::Added,
+        Self::Changed,
+        Self::Failed,
+        Self::Moved,
+        Self::Removed,
+    ];
 
     const fn as_str(self) -> &'static str {
         match self {
+            Self::Aborted => "aborted",
             Self::Added => "added",
             Self::Changed => "changed",
+            Self::Failed => "failed",
             Self::Moved => "moved",
             Self::Removed => "removed",
             Self::Same => "same",
@@ -61,6 +75,10 @@ pub fn write_change_summary<W: std::io::Write>(
 #[cfg(test)]
 fn generate_every_type_once() -> Vec<Changestatus> {
     vec![
+        Changestatus {
+            name: String::from("Ab"),
+            changetype: Changetype::Aborted,
+        },
         Changestatus {
             name: String::from("A"),
             changetype: Changetype::Added,
@@ -69,6 +87,10 @@ fn generate_every_type_once() -> Vec<Changestatus> {
             name: String::from("C"),
             changetype: Changetype::Changed,
         },
+        Changestatus {
+            name: String::from("F"),
+            changetype: Changetype::Failed,
+        },
         Changestatus {
             name: String::from("M"),
             changetype: Changetype::Moved,
@@ -102,8 +124,10 @@ fn summary_shows_every_type_once() {
     let result = String::from_utf8(result).unwrap();
     assert_eq!(
         result,
-        r#"added   (   1): ["A"]
+        r#"aborted (   1): ["Ab"]
+added   (   1): ["A"]
 changed (   1): ["C"]
+failed  (   1): ["F"]
 moved   (   1): ["M"]
 removed (   1): ["R"]
 same    (   1): ["Sa"]
@@ -124,8 +148,10 @@ fn summary_shows_interesting_types_once() {
     let result = String::from_utf8(result).unwrap();
     assert_eq!(
         result,
-        r#"added   (   1): ["A"]
+        r#"aborted (   1): ["Ab"]
+added   (   1): ["A"]
 changed (   1): ["C"]
+failed  (   1): ["F"]
 moved   (   1): ["M"]
 removed (   1): ["R"]
 "#
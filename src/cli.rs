@@ -0,0 +1,192 @@
+//! Subcommands rendering something of a single chat instead of running the continuous build.
+
+use anyhow::Context as _;
+use chrono::{Datelike as _, IsoWeek, NaiveDate, NaiveDateTime, Weekday};
+
+use crate::generate_agenda::{Format, Period, agenda_of_chat};
+use crate::generate_csv::{csv_of_chat, generate_csv};
+use crate::generate_svg::week_svg;
+use crate::resolved_events::all_eventfile_events;
+use crate::{berlin_time, free_rooms};
+
+const USAGE: &str = "Usage:
+  hawhh-calendarbot-parser [--force-cleanup]
+  hawhh-calendarbot-parser svg <chat id> [week]
+  hawhh-calendarbot-parser agenda <chat id> [today|tomorrow|week|<date>|<week>] [--markdown]
+  hawhh-calendarbot-parser csv <chat id|--all>
+  hawhh-calendarbot-parser free-rooms [building] [--at <date time>] [--minutes <minutes>]
+
+week: ISO week like 2020-W15 or only 15 in the current year. Defaults to the current week.
+date: like 2020-04-06
+--markdown: Telegram MarkdownV2 instead of plain text
+--all: every event of every eventfile without changes of users
+date time: like 2020-04-06T10:00. Defaults to now.
+minutes: the room has to be free for at least this long. Defaults to 0.";
+
+pub fn run(command: &str, args: &[String]) -> anyhow::Result<()> {
+    match command {
+        "svg" => svg(args),
+        "agenda" => agenda(args),
+        "csv" => csv(args),
+        "free-rooms" => free_rooms(args),
+        "help" | "--help" | "-h" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        _ => anyhow::bail!("unknown command {command}\n\n{USAGE}"),
+    }
+}
+
+/// Print the timetable of the week as SVG
+fn svg(args: &[String]) -> anyhow::Result<()> {
+    let (chat_id, week) = match args {
+        [chat_id] => (chat_id, None),
+        [chat_id, week] => (chat_id, Some(week)),
+        _ => anyhow::bail!("svg expects a chat id and optionally a week\n\n{USAGE}"),
+    };
+    let chat_id = chat_id.parse().context("chat id should be a number")?;
+    let today = berlin_time::now().date();
+    let week = match week {
+        Some(week) => parse_iso_week(week, today)
+            .with_context(|| format!("week {week} should be like 2020-W15 or 15"))?,
+        None => today.iso_week(),
+    };
+    print!("{}", week_svg(chat_id, week)?);
+    Ok(())
+}
+
+/// Print the agenda of a day or week
+fn agenda(args: &[String]) -> anyhow::Result<()> {
+    let markdown = args.iter().any(|arg| arg == "--markdown");
+    let args = args
+        .iter()
+        .filter(|arg| *arg != "--markdown")
+        .collect::<Vec<_>>();
+    let (chat_id, period) = match args.as_slice() {
+        [chat_id] => (chat_id, None),
+        [chat_id, period] => (chat_id, Some(period)),
+        _ => anyhow::bail!("agenda expects a chat id and optionally a day or week\n\n{USAGE}"),
+    };
+    let chat_id = chat_id.parse().context("chat id should be a number")?;
+    let today = berlin_time::now().date();
+    let period = match period {
+        Some(period) => parse_period(period, today)
+            .with_context(|| format!("{period} should be a day or a week"))?,
+        None => Period::Day(today),
+    };
+    let format = if markdown {
+        Format::Markdown
+    } else {
+        Format::Text
+    };
+    print!("{}", agenda_of_chat(chat_id, period, format)?);
+    Ok(())
+}
+
+/// Print the events as CSV
+fn csv(args: &[String]) -> anyhow::Result<()> {
+    let [target] = args else {
+        anyhow::bail!("csv expects a chat id or --all\n\n{USAGE}");
+    };
+    let csv = if target == "--all" {
+        generate_csv(&all_eventfile_events())
+    } else {
+        csv_of_chat(target.parse().context("chat id should be a number")?)?
+    };
+    print!("{csv}");
+    Ok(())
+}
+
+/// Print the rooms free at the given time
+fn free_rooms(args: &[String]) -> anyhow::Result<()> {
+    let mut building = None;
+    let mut at = None;
+    let mut minutes = 0;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--at" => {
+                let value = args.next().context("--at expects a date time")?;
+                at = Some(
+                    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+                        .with_context(|| format!("{value} should be like 2020-04-06T10:00"))?,
+                );
+            }
+            "--minutes" => {
+                let value = args.next().context("--minutes expects a number")?;
+                minutes = value.parse().context("minutes should be a number")?;
+            }
+            _ if building.is_none() && !arg.starts_with("--") => building = Some(arg.as_str()),
+            _ => anyhow::bail!("unexpected argument {arg}\n\n{USAGE}"),
+        }
+    }
+
+    let at = at.unwrap_or_else(berlin_time::now);
+    let rooms = free_rooms::query(building, at, minutes)?;
+    if rooms.is_empty() {
+        println!("No free rooms at {}.", at.format("%Y-%m-%d %H:%M"));
+    }
+    for room in rooms {
+        println!("{} until {}", room.room, room.until.format("%H:%M"));
+    }
+    Ok(())
+}
+
+fn parse_period(value: &str, today: NaiveDate) -> Option<Period> {
+    match value {
+        "today" => Some(Period::Day(today)),
+        "tomorrow" => today.succ_opt().map(Period::Day),
+        "week" => Some(Period::Week(today.iso_week())),
+        _ => NaiveDate::parse_from_str(value, "%Y-%m-%d").map_or_else(
+            |_| parse_iso_week(value, today).map(Period::Week),
+            |date| Some(Period::Day(date)),
+        ),
+    }
+}
+
+/// `2020-W15` or only the week `15` within the ISO year of today
+fn parse_iso_week(value: &str, today: NaiveDate) -> Option<IsoWeek> {
+    let (year, week) = match value.split_once("-W") {
+        Some((year, week)) => (year.parse().ok()?, week),
+        None => (today.iso_week().year(), value),
+    };
+    let week = week.parse().ok()?;
+    NaiveDate::from_isoywd_opt(year, week, Weekday::Mon).map(|monday| monday.iso_week())
+}
+
+#[test]
+fn parse_iso_week_examples() {
+    let today = NaiveDate::from_ymd_opt(2020, 4, 6).unwrap();
+    let week = |year: i32, week: u32| {
+        NaiveDate::from_isoywd_opt(year, week, Weekday::Mon)
+            .unwrap()
+            .iso_week()
+    };
+    assert_eq!(parse_iso_week("2020-W15", today), Some(week(2020, 15)));
+    assert_eq!(parse_iso_week("2021-W01", today), Some(week(2021, 1)));
+    assert_eq!(parse_iso_week("15", today), Some(week(2020, 15)));
+    assert_eq!(parse_iso_week("2020-W54", today), None);
+    assert_eq!(parse_iso_week("0", today), None);
+    assert_eq!(parse_iso_week("next", today), None);
+}
+
+#[test]
+fn parse_period_examples() {
+    let today = NaiveDate::from_ymd_opt(2020, 4, 6).unwrap();
+    let tomorrow = NaiveDate::from_ymd_opt(2020, 4, 7).unwrap();
+    assert_eq!(parse_period("today", today), Some(Period::Day(today)));
+    assert_eq!(parse_period("tomorrow", today), Some(Period::Day(tomorrow)));
+    assert_eq!(
+        parse_period("week", today),
+        Some(Period::Week(today.iso_week()))
+    );
+    assert_eq!(
+        parse_period("2020-04-07", today),
+        Some(Period::Day(tomorrow))
+    );
+    assert_eq!(
+        parse_period("2020-W15", today),
+        Some(Period::Week(today.iso_week()))
+    );
+    assert_eq!(parse_period("yesterday", today), None);
+}
@@ -0,0 +1,112 @@
+//! Overlapping events of different eventfiles within the calendar of a user like two modules with colliding slots.
+
+use chrono::NaiveDateTime;
+
+use crate::generate_ics::EventStatus;
+use crate::resolved_events::ResolvedEvent;
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConflictEvent<'resolved> {
+    pub name: &'resolved str,
+    pub eventfile: &'resolved str,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub location: &'resolved str,
+}
+
+/// Two events overlapping in time, the first one starts first
+#[derive(Debug, PartialEq, Eq, serde::Serialize)]
+pub struct Conflict<'resolved> {
+    pub first: ConflictEvent<'resolved>,
+    pub second: ConflictEvent<'resolved>,
+}
+
+impl<'resolved> From<&'resolved ResolvedEvent> for ConflictEvent<'resolved> {
+    fn from(resolved: &'resolved ResolvedEvent) -> Self {
+        Self {
+            name: &resolved.event.name,
+            eventfile: &resolved.eventfile,
+            start_time: resolved.event.start_time,
+            end_time: resolved.event.end_time,
+            location: &resolved.event.location,
+        }
+    }
+}
+
+/// Overlaps of events from different eventfiles.
+/// Cancelled events do not take place and all day events like exam periods would overlap everything.
+pub fn find_conflicts(events: &[ResolvedEvent]) -> Vec<Conflict<'_>> {
+    let mut relevant = events
+        .iter()
+        .filter(|resolved| {
+            resolved.event.status != EventStatus::Cancelled && !resolved.event.all_day
+        })
+        .collect::<Vec<_>>();
+    relevant.sort_by_key(|resolved| resolved.event.start_time);
+
+    let mut result = Vec::new();
+    for (i, first) in relevant.iter().enumerate() {
+        for second in relevant[i + 1..]
+            .iter()
+            .take_while(|second| second.event.start_time < first.event.end_time)
+        {
+            if first.eventfile != second.eventfile {
+                result.push(Conflict {
+                    first: (*first).into(),
+                    second: (*second).into(),
+                });
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+fn create_event(eventfile: &str, day: u32, hour: u32, minutes: i64) -> ResolvedEvent {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 4, day)
+        .unwrap()
+        .and_hms_opt(hour, 15, 0)
+        .unwrap();
+    crate::resolved_events::test_event(eventfile, start_time, minutes)
+}
+
+#[test]
+fn overlaps_of_different_eventfiles_are_conflicts() {
+    let events = [
+        create_event("BTI5-VS", 2, 8, 180),
+        create_event("BTI5-VSP/01", 2, 10, 180),
+        create_event("BTI5-VSP/01", 2, 11, 60),
+        // Back to back is no overlap
+        create_event("BTI5-SE", 2, 14, 60),
+        create_event("BTI5-VS", 3, 8, 180),
+    ];
+    let conflicts = find_conflicts(&events);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].first.eventfile, "BTI5-VS");
+    assert_eq!(conflicts[0].second.eventfile, "BTI5-VSP/01");
+    assert_eq!(conflicts[0].second.start_time, events[1].event.start_time);
+}
+
+#[test]
+fn cancelled_and_all_day_events_do_not_conflict() {
+    let mut cancelled = create_event("BTI5-VSP/01", 2, 9, 60);
+    cancelled.event.status = EventStatus::Cancelled;
+    let mut all_day = create_event("Prüfungszeitraum", 2, 0, 60);
+    all_day.event.all_day = true;
+    let events = [create_event("BTI5-VS", 2, 8, 180), cancelled, all_day];
+    assert!(find_conflicts(&events).is_empty());
+}
+
+#[test]
+fn conflicts_are_serialized_for_the_bot() {
+    let events = [
+        create_event("BTI5-VS", 2, 8, 180),
+        create_event("BTI5-SE", 2, 9, 60),
+    ];
+    let json = serde_json::to_string(&find_conflicts(&events)).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"first":{"name":"BTI5-VS","eventfile":"BTI5-VS","startTime":"2020-04-02T08:15:00","endTime":"2020-04-02T11:15:00","location":"1060"},"second":{"name":"BTI5-SE","eventfile":"BTI5-SE","startTime":"2020-04-02T09:15:00","endTime":"2020-04-02T10:15:00","location":"1060"}}]"#
+    );
+}
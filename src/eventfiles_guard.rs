@@ -0,0 +1,229 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Refuse eventfile updates which drop more than this percentage of events.
+const DEFAULT_MAX_DROP_PERCENT: u8 = 20;
+
+/// Files with fewer events are only covered by the total count.
+/// A few removed events in a small file are not suspicious.
+const MIN_EVENTS_FOR_FILE_CHECK: usize = 10;
+
+/// Eventfile name (relative to the events folder, without .json) to its number of events
+pub type EventCounts = BTreeMap<String, usize>;
+
+#[derive(Debug)]
+pub struct Guard {
+    pub max_drop_percent: u8,
+    /// The operator accepted this commit even when it drops events
+    pub accepted_commit: Option<String>,
+}
+
+impl Guard {
+    /// `EVENTFILES_MAX_DROP_PERCENT` configures the threshold.
+    /// `EVENTFILES_ACCEPT_COMMIT` overrides the guard for exactly that commit.
+    pub fn from_env() -> Self {
+        let max_drop_percent = std::env::var("EVENTFILES_MAX_DROP_PERCENT")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_MAX_DROP_PERCENT);
+        let accepted_commit = std::env::var("EVENTFILES_ACCEPT_COMMIT")
+            .ok()
+            .filter(|value| !value.is_empty());
+        Self {
+            max_drop_percent,
+            accepted_commit,
+        }
+    }
+
+    /// Compare the counts before and after an update.
+    /// Returns an error describing the drops when the update should not be published.
+    pub fn check(
+        &self,
+        commit: &str,
+        before: &EventCounts,
+        after: &EventCounts,
+    ) -> anyhow::Result<()> {
+        let mut drops = Vec::new();
+
+        let total_before = before.values().sum::<usize>();
+        let total_after = after.values().sum::<usize>();
+        if self.is_drop_too_large(total_before, total_after) {
+            drops.push(format!("total {total_before} -> {total_after}"));
+        }
+
+        for (filename, &count_before) in before {
+            let Some(&count_after) = after.get(filename) else {
+                // Removed eventfiles are only covered by the total count
+                continue;
+            };
+            if count_before >= MIN_EVENTS_FOR_FILE_CHECK
+                && self.is_drop_too_large(count_before, count_after)
+            {
+                drops.push(format!("{filename} {count_before} -> {count_after}"));
+            }
+        }
+
+        if drops.is_empty() {
+            return Ok(());
+        }
+        if self
+            .accepted_commit
+            .as_ref()
+            .is_some_and(|accepted| commit.starts_with(accepted.as_str()))
+        {
+            println!(
+                "eventfiles commit {commit} drops events but was accepted by the operator: {}",
+                drops.join(", ")
+            );
+            return Ok(());
+        }
+
+        anyhow::bail!(
+            "eventfiles commit {commit} drops more than {}% of events ({}). Set EVENTFILES_ACCEPT_COMMIT={commit} to publish it anyway.",
+            self.max_drop_percent,
+            drops.join(", ")
+        );
+    }
+
+    fn is_drop_too_large(&self, before: usize, after: usize) -> bool {
+        let dropped = before.saturating_sub(after);
+        dropped * 100 > before * usize::from(self.max_drop_percent)
+    }
+}
+
+/// Count the events of every eventfile in the events folder.
+/// Unreadable eventfiles count as empty.
+pub fn count_events(events_folder: &Path) -> EventCounts {
+    let mut counts = EventCounts::new();
+    count_events_recursive(events_folder, events_folder, &mut counts);
+    counts
+}
+
+fn count_events_recursive(root: &Path, folder: &Path, counts: &mut EventCounts) {
+    let Ok(entries) = fs::read_dir(folder) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            count_events_recursive(root, &path, counts);
+            continue;
+        }
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Some(name) = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.with_extension("").to_str().map(ToOwned::to_owned))
+        else {
+            continue;
+        };
+        let count = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<Vec<serde::de::IgnoredAny>>(&content).ok())
+            .map_or(0, |events| events.len());
+        counts.insert(name, count);
+    }
+}
+
+#[cfg(test)]
+fn counts(list: &[(&str, usize)]) -> EventCounts {
+    list.iter()
+        .map(|(name, count)| ((*name).to_owned(), *count))
+        .collect()
+}
+
+#[cfg(test)]
+const fn guard(accepted_commit: Option<String>) -> Guard {
+    Guard {
+        max_drop_percent: 20,
+        accepted_commit,
+    }
+}
+
+#[test]
+fn unchanged_counts_pass() {
+    let before = counts(&[("a", 20), ("b", 30)]);
+    guard(None).check("abc", &before, &before).unwrap();
+}
+
+#[test]
+fn more_events_pass() {
+    let before = counts(&[("a", 20)]);
+    let after = counts(&[("a", 25), ("b", 30)]);
+    guard(None).check("abc", &before, &after).unwrap();
+}
+
+#[test]
+fn small_drop_passes() {
+    let before = counts(&[("a", 50), ("b", 50)]);
+    let after = counts(&[("a", 45), ("b", 50)]);
+    guard(None).check("abc", &before, &after).unwrap();
+}
+
+#[test]
+fn emptied_eventfiles_are_refused() {
+    let before = counts(&[("a", 50), ("b", 50)]);
+    let after = counts(&[("a", 0), ("b", 0)]);
+    let error = guard(None).check("abc", &before, &after).unwrap_err();
+    let message = error.to_string();
+    assert!(message.contains("total 100 -> 0"), "{message}");
+    assert!(message.contains("a 50 -> 0"), "{message}");
+}
+
+#[test]
+fn truncated_single_file_is_refused() {
+    let before = counts(&[("a", 20), ("b", 500)]);
+    let after = counts(&[("a", 3), ("b", 500)]);
+    let error = guard(None).check("abc", &before, &after).unwrap_err();
+    assert!(error.to_string().contains("a 20 -> 3"));
+}
+
+#[test]
+fn small_file_drop_is_only_covered_by_total() {
+    let before = counts(&[("a", 4), ("b", 500)]);
+    let after = counts(&[("a", 1), ("b", 500)]);
+    guard(None).check("abc", &before, &after).unwrap();
+}
+
+#[test]
+fn removed_files_are_only_covered_by_total() {
+    let before = counts(&[("a", 50), ("b", 500)]);
+    let after = counts(&[("b", 500)]);
+    guard(None).check("abc", &before, &after).unwrap();
+
+    let after = counts(&[("a", 50)]);
+    assert!(guard(None).check("abc", &before, &after).is_err());
+}
+
+#[test]
+fn accepted_commit_passes() {
+    let before = counts(&[("a", 50)]);
+    let after = counts(&[("a", 0)]);
+    guard(Some("abc".to_owned()))
+        .check("abcdef", &before, &after)
+        .unwrap();
+    assert!(
+        guard(Some("xyz".to_owned()))
+            .check("abcdef", &before, &after)
+            .is_err()
+    );
+}
+
+#[test]
+fn count_events_of_folder() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let folder = tempdir.path();
+    fs::create_dir(folder.join("sub")).unwrap();
+    fs::write(folder.join("a.json"), "[{}, {}]").unwrap();
+    fs::write(folder.join("sub/b.json"), "[{}]").unwrap();
+    fs::write(folder.join("broken.json"), "[{").unwrap();
+    fs::write(folder.join("readme.md"), "hi").unwrap();
+
+    assert_eq!(
+        count_events(folder),
+        counts(&[("a", 2), ("broken", 0), ("sub/b", 1)])
+    );
+}
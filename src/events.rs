@@ -1,51 +1,224 @@
 use std::fs;
 use std::path::Path;
 use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
 
 use anyhow::Context as _;
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Days, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 
-use crate::generate_ics::{EventStatus, SoonToBeIcsEvent};
+use crate::eventfiles_guard::{Guard, count_events};
+use crate::generate_ics::{EventMeta, EventStatus, EventType, SoonToBeIcsEvent};
+use crate::lecturers::lecturers_of;
 
+/// Unknown fields are ignored, so eventfiles can gain fields before the parser knows them
 #[derive(Debug, serde::Deserialize)]
+#[cfg_attr(test, derive(Default))]
 #[serde(rename_all = "camelCase")]
 pub struct EventEntry {
     pub name: String,
     pub location: String,
     pub description: String,
-    pub start_time: NaiveDateTime,
-    pub end_time: NaiveDateTime,
+    pub start_time: EventTime,
+    pub end_time: EventTime,
+
+    #[serde(default)]
+    pub event_type: Option<EventType>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Abbreviations like `HTM` or `HTM, KSS`
+    #[serde(default)]
+    pub lecturer: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub all_day: bool,
+    #[serde(default)]
+    pub original_id: Option<String>,
+}
+
+/// Start or end of an event. Dates without a time are whole days.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(untagged)]
+pub enum EventTime {
+    DateTime(NaiveDateTime),
+    /// As end the last day of the event
+    Date(NaiveDate),
+}
+
+/// Tests only spell out the fields they care about
+#[cfg(test)]
+impl Default for EventTime {
+    fn default() -> Self {
+        Self::DateTime(NaiveDateTime::default())
+    }
 }
 
 pub const FOLDER: &str = "eventfiles";
+const REMOTE: &str = "https://github.com/HAWHHCalendarBot/eventfiles.git";
+
+/// Delays before retrying a pull which failed to fetch. One more attempt than delays is made.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_secs(10),
+    Duration::from_mins(1),
+    Duration::from_mins(5),
+];
+
+/// State of the eventfiles checkout and the pulls updating it.
+#[derive(Debug, Default)]
+pub struct PullStatus {
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub current_commit: Option<String>,
+    /// Fetching failed and is retried after this point in time
+    retry_at: Option<Instant>,
+    failed_fetches: usize,
+}
+
+impl PullStatus {
+    /// Pull the eventfiles once. A failed fetch schedules a retry with backoff, see [`Self::retry_due`].
+    ///
+    /// A failed pull keeps the last good checkout in place, so calendars can still be built from it.
+    /// An update refused by the guard is reverted to the last good checkout.
+    pub fn pull(&mut self, guard: &Guard) -> anyhow::Result<()> {
+        self.pull_from(Path::new(FOLDER), REMOTE, &RETRY_DELAYS, guard)
+    }
+
+    /// Is a retry of a failed fetch scheduled and due?
+    pub fn retry_due(&self) -> bool {
+        self.retry_at.is_some_and(|at| at <= Instant::now())
+    }
 
-pub fn pull() {
-    if Path::new(FOLDER).join(".git").exists() {
-        let status = Command::new("git")
-            .args(["pull", "--ff-only"])
-            .current_dir(FOLDER)
-            .status()
-            .expect("git process should execute");
-        assert!(status.success(), "git pull status code {status}");
+    fn pull_from(
+        &mut self,
+        folder: &Path,
+        remote: &str,
+        delays: &[Duration],
+        guard: &Guard,
+    ) -> anyhow::Result<()> {
+        let previous_commit = current_commit(folder).ok();
+        let before = count_events(&folder.join("events"));
+
+        // Only the network is worth a retry, a history not fast-forwarding stays that way
+        let fetched = fetch(folder, remote);
+        let transient = fetched.is_err();
+        let mut result = fetched.and_then(|()| fast_forward(folder));
+
+        if result.is_ok()
+            && let Some(previous_commit) = previous_commit
+            && let Ok(commit) = current_commit(folder)
+            && commit != previous_commit
+        {
+            let after = count_events(&folder.join("events"));
+            if let Err(err) = guard.check(&commit, &before, &after) {
+                result = git(folder, &["reset", "-q", "--hard", &previous_commit])
+                    .context("failed to revert to the last good checkout")
+                    .and(Err(err));
+            }
+        }
+
+        match &result {
+            Ok(()) => {
+                self.last_success = Some(SystemTime::now().into());
+                self.last_error = None;
+            }
+            Err(err) => self.last_error = Some(format!("{err:#}")),
+        }
+        let delay = delays
+            .get(self.failed_fetches)
+            .filter(|_| result.is_err() && transient);
+        self.retry_at = delay.map(|delay| Instant::now() + *delay);
+        self.failed_fetches = if delay.is_some() {
+            self.failed_fetches + 1
+        } else {
+            0
+        };
+        self.current_commit = current_commit(folder).ok();
+
+        result
+    }
+}
+
+impl std::fmt::Display for PullStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "eventfiles commit: ")?;
+        match &self.current_commit {
+            Some(commit) => write!(f, "{commit}")?,
+            None => write!(f, "none")?,
+        }
+        write!(f, ", last success: ")?;
+        match self.last_success {
+            Some(time) => write!(f, "{}", time.format("%Y-%m-%d %H:%M:%S UTC"))?,
+            None => write!(f, "never")?,
+        }
+        if let Some(err) = &self.last_error {
+            write!(f, ", last error: {err}")?;
+        }
+        if let Some(at) = self.retry_at {
+            let delay = at.saturating_duration_since(Instant::now());
+            write!(f, ", retry in {}s", delay.as_secs())?;
+        }
+        Ok(())
+    }
+}
+
+/// Is there a checkout calendars can be built from?
+pub fn checkout_exists() -> bool {
+    Path::new(FOLDER).join(".git").exists()
+}
+
+fn fetch(folder: &Path, remote: &str) -> anyhow::Result<()> {
+    if folder.join(".git").exists() {
+        git(folder, &["fetch", "-q"]).context("git fetch failed")
     } else {
-        let status = Command::new("git")
-            .args([
-                "clone",
-                "-q",
-                "--depth",
-                "1",
-                "https://github.com/HAWHHCalendarBot/eventfiles.git",
-                FOLDER,
-            ])
-            .status()
-            .expect("git process should execute");
-        assert!(status.success(), "git clone status code {status}");
+        let folder = folder
+            .to_str()
+            .context("eventfiles folder should be UTF8")?;
+        git(
+            Path::new("."),
+            &["clone", "-q", "--depth", "1", remote, folder],
+        )
+        .context("git clone failed")
     }
 }
 
+fn fast_forward(folder: &Path) -> anyhow::Result<()> {
+    git(folder, &["merge", "-q", "--ff-only", "@{upstream}"]).context("git merge failed")
+}
+
+fn current_commit(folder: &Path) -> anyhow::Result<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "HEAD"])
+        .current_dir(folder)
+        .output()
+        .context("git process should execute")?;
+    anyhow::ensure!(output.status.success(), "git rev-parse {}", output.status);
+    Ok(String::from_utf8(output.stdout)?.trim().to_owned())
+}
+
+fn git(current_dir: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(current_dir)
+        .output()
+        .context("git process should execute")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "{}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+    Ok(())
+}
+
 pub fn read(filename: &str) -> anyhow::Result<Vec<EventEntry>> {
     let mut path = Path::new(FOLDER).join("events").join(filename);
     path.set_extension("json");
+    read_file(&path)
+}
+
+pub fn read_file(path: &Path) -> anyhow::Result<Vec<EventEntry>> {
     let content = fs::read_to_string(path).context("failed to read")?;
     let event_entries: Vec<EventEntry> =
         serde_json::from_str(&content).context("failed to parse")?;
@@ -55,12 +228,31 @@ pub fn read(filename: &str) -> anyhow::Result<Vec<EventEntry>> {
 
 impl From<EventEntry> for SoonToBeIcsEvent {
     fn from(event: EventEntry) -> Self {
+        let start_time = match event.start_time {
+            EventTime::DateTime(time) => time,
+            EventTime::Date(date) => date.and_time(NaiveTime::MIN),
+        };
+        // The end of the last day is the start of the next day
+        let end_time = match event.end_time {
+            EventTime::DateTime(time) => time,
+            EventTime::Date(date) => (date + Days::new(1)).and_time(NaiveTime::MIN),
+        };
         Self {
-            start_time: event.start_time,
-            end_time: event.end_time,
+            start_time,
+            end_time,
             name: event.name,
             status: EventStatus::Confirmed,
             alert_minutes_before: None,
+            lecturers: lecturers_of(event.lecturer.as_deref(), &event.description),
+            all_day: event.all_day || matches!(event.start_time, EventTime::Date(_)),
+            transparent: false,
+            meta: EventMeta {
+                event_type: event.event_type,
+                url: event.url.filter(|url| !url.is_empty()),
+                categories: event.categories,
+                group: event.group,
+                original_id: event.original_id,
+            },
             description: event.description,
             location: event.location,
         }
@@ -69,8 +261,6 @@ impl From<EventEntry> for SoonToBeIcsEvent {
 
 #[test]
 fn can_deserialize_event_entry() -> Result<(), serde_json::Error> {
-    use chrono::NaiveDate;
-
     let test: EventEntry = serde_json::from_str(
         r#"{"name": "BTI1-TI", "location": "1060", "description": "Dozent: HTM", "startTime": "2022-01-13T11:40:00", "endTime": "2022-01-13T12:00:00"}"#,
     )?;
@@ -80,18 +270,251 @@ fn can_deserialize_event_entry() -> Result<(), serde_json::Error> {
     assert_eq!(test.description, "Dozent: HTM");
     assert_eq!(
         test.start_time,
-        NaiveDate::from_ymd_opt(2022, 1, 13)
-            .unwrap()
-            .and_hms_opt(11, 40, 0)
-            .unwrap()
+        EventTime::DateTime(
+            NaiveDate::from_ymd_opt(2022, 1, 13)
+                .unwrap()
+                .and_hms_opt(11, 40, 0)
+                .unwrap()
+        )
     );
     assert_eq!(
         test.end_time,
-        NaiveDate::from_ymd_opt(2022, 1, 13)
-            .unwrap()
-            .and_hms_opt(12, 0, 0)
-            .unwrap()
+        EventTime::DateTime(
+            NaiveDate::from_ymd_opt(2022, 1, 13)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        )
     );
 
     Ok(())
 }
+
+#[test]
+fn lecturers_are_parsed_from_description() -> Result<(), serde_json::Error> {
+    let entry: EventEntry = serde_json::from_str(
+        r#"{"name": "BTI1-TI", "location": "1060", "description": "Dozent: HTM", "startTime": "2022-01-13T11:40:00", "endTime": "2022-01-13T12:00:00"}"#,
+    )?;
+    let event = SoonToBeIcsEvent::from(entry);
+    assert_eq!(event.lecturers, ["HTM"]);
+    assert_eq!(event.description, "Dozent: HTM");
+    Ok(())
+}
+
+#[test]
+fn can_deserialize_event_entry_with_metadata() -> Result<(), serde_json::Error> {
+    let entry: EventEntry = serde_json::from_str(
+        r#"{"name": "BTI1-TI", "location": "1060", "description": "Dozent: HTM", "startTime": "2022-01-13T11:40:00", "endTime": "2022-01-13T12:00:00",
+        "eventType": "lab", "url": "https://www.haw-hamburg.de/ti", "categories": ["Pflicht"], "lecturer": "KSS", "group": "BTI1-TI/01", "allDay": false, "originalId": "42", "futureField": 1}"#,
+    )?;
+    let event = SoonToBeIcsEvent::from(entry);
+    assert_eq!(event.lecturers, ["KSS", "HTM"]);
+    assert!(!event.all_day);
+    assert_eq!(
+        event.meta,
+        EventMeta {
+            event_type: Some(EventType::Lab),
+            url: Some("https://www.haw-hamburg.de/ti".to_owned()),
+            categories: vec!["Pflicht".to_owned()],
+            group: Some("BTI1-TI/01".to_owned()),
+            original_id: Some("42".to_owned()),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn dates_without_time_are_all_day() -> Result<(), serde_json::Error> {
+    let entry: EventEntry = serde_json::from_str(
+        r#"{"name": "Prüfungszeitraum", "location": "", "description": "", "startTime": "2022-02-01", "endTime": "2022-02-04"}"#,
+    )?;
+    let event = SoonToBeIcsEvent::from(entry);
+    assert!(event.all_day);
+    let day = |day: u32| NaiveDate::from_ymd_opt(2022, 2, day).unwrap();
+    assert_eq!(event.start_time, day(1).and_time(NaiveTime::MIN));
+    assert_eq!(event.end_time, day(5).and_time(NaiveTime::MIN));
+    assert_eq!(crate::generate_ics::all_day_end(&event), day(5));
+    Ok(())
+}
+
+#[test]
+fn unknown_event_type_is_other() -> Result<(), serde_json::Error> {
+    let entry: EventEntry = serde_json::from_str(
+        r#"{"name": "BTI1-TI", "location": "", "description": "", "startTime": "2022-01-13T11:40:00", "endTime": "2022-01-13T12:00:00", "eventType": "excursion"}"#,
+    )?;
+    assert_eq!(entry.event_type, Some(EventType::Other));
+    Ok(())
+}
+
+#[cfg(test)]
+const TEST_GUARD: Guard = Guard {
+    max_drop_percent: 20,
+    accepted_commit: None,
+};
+
+#[cfg(test)]
+fn git_commit_file(repo: &Path, filename: &str, content: &str) {
+    fs::write(repo.join(filename), content).unwrap();
+    git(repo, &["add", filename]).unwrap();
+    git(
+        repo,
+        &[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-q",
+            "-m",
+            filename,
+        ],
+    )
+    .unwrap();
+}
+
+/// Creates a bare repository with one commit and returns a working copy pushing into it.
+#[cfg(test)]
+fn create_remote(tempdir: &Path) -> (String, std::path::PathBuf) {
+    let bare = tempdir.join("remote.git");
+    let upstream = tempdir.join("upstream");
+    git(tempdir, &["init", "-q", "--bare", bare.to_str().unwrap()]).unwrap();
+    git(
+        tempdir,
+        &[
+            "clone",
+            "-q",
+            bare.to_str().unwrap(),
+            upstream.to_str().unwrap(),
+        ],
+    )
+    .unwrap();
+    git_commit_file(&upstream, "first", "1");
+    git(&upstream, &["push", "-q", "origin", "HEAD"]).unwrap();
+    (format!("file://{}", bare.display()), upstream)
+}
+
+#[test]
+fn pull_clones_and_updates_from_remote() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let (remote, upstream) = create_remote(tempdir.path());
+    let folder = tempdir.path().join("eventfiles");
+
+    let mut status = PullStatus::default();
+    status
+        .pull_from(&folder, &remote, &[], &TEST_GUARD)
+        .unwrap();
+    assert!(folder.join("first").exists());
+    assert!(status.last_success.is_some());
+    assert_eq!(status.last_error, None);
+    let first_commit = status.current_commit.clone().unwrap();
+
+    git_commit_file(&upstream, "second", "2");
+    git(&upstream, &["push", "-q", "origin", "HEAD"]).unwrap();
+
+    status
+        .pull_from(&folder, &remote, &[], &TEST_GUARD)
+        .unwrap();
+    assert!(folder.join("second").exists());
+    assert_ne!(status.current_commit.unwrap(), first_commit);
+}
+
+#[test]
+fn failed_pull_keeps_last_good_checkout() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let (remote, upstream) = create_remote(tempdir.path());
+    let folder = tempdir.path().join("eventfiles");
+
+    let mut status = PullStatus::default();
+    status
+        .pull_from(&folder, &remote, &[], &TEST_GUARD)
+        .unwrap();
+    let good_commit = status.current_commit.clone().unwrap();
+    let good_time = status.last_success;
+
+    // Rewrite the upstream history so the next pull is not a fast-forward
+    git(
+        &upstream,
+        &[
+            "-c",
+            "user.name=test",
+            "-c",
+            "user.email=test@example.com",
+            "commit",
+            "-q",
+            "--amend",
+            "-m",
+            "rewritten",
+        ],
+    )
+    .unwrap();
+    git(&upstream, &["push", "-q", "--force", "origin", "HEAD"]).unwrap();
+
+    let result = status.pull_from(&folder, &remote, &[Duration::ZERO], &TEST_GUARD);
+    assert!(result.is_err());
+    assert!(!status.retry_due(), "not fast-forwarding is not retried");
+    assert!(status.last_error.is_some());
+    assert_eq!(status.last_success, good_time);
+    assert_eq!(status.current_commit.unwrap(), good_commit);
+    assert!(folder.join("first").exists());
+}
+
+#[test]
+fn failed_clone_reports_error() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let folder = tempdir.path().join("eventfiles");
+    let remote = format!("file://{}", tempdir.path().join("missing.git").display());
+
+    let mut status = PullStatus::default();
+    let result = status.pull_from(&folder, &remote, &[Duration::ZERO], &TEST_GUARD);
+    assert!(result.is_err());
+    assert!(status.last_error.is_some());
+    assert_eq!(status.last_success, None);
+    assert_eq!(status.current_commit, None);
+    assert!(status.retry_due());
+
+    // Retries are exhausted, the next regular pull starts over
+    let result = status.pull_from(&folder, &remote, &[Duration::ZERO], &TEST_GUARD);
+    assert!(result.is_err());
+    assert!(!status.retry_due());
+    assert_eq!(status.failed_fetches, 0);
+}
+
+#[test]
+fn pull_dropping_events_is_reverted() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let (remote, upstream) = create_remote(tempdir.path());
+    fs::create_dir(upstream.join("events")).unwrap();
+    git_commit_file(
+        &upstream,
+        "events/a.json",
+        &format!("[{}]", ["{}"; 20].join(",")),
+    );
+    git(&upstream, &["push", "-q", "origin", "HEAD"]).unwrap();
+    let folder = tempdir.path().join("eventfiles");
+
+    let mut status = PullStatus::default();
+    status
+        .pull_from(&folder, &remote, &[], &TEST_GUARD)
+        .unwrap();
+    let good_commit = status.current_commit.clone().unwrap();
+
+    git_commit_file(&upstream, "events/a.json", "[]");
+    git(&upstream, &["push", "-q", "origin", "HEAD"]).unwrap();
+
+    let error = status
+        .pull_from(&folder, &remote, &[], &TEST_GUARD)
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("drops more than 20%"),
+        "{error:#}"
+    );
+    assert_eq!(status.current_commit.clone().unwrap(), good_commit);
+    assert_eq!(count_events(&folder.join("events"))["a"], 20);
+
+    let accepting = Guard {
+        max_drop_percent: 20,
+        accepted_commit: Some(current_commit(&upstream).unwrap()),
+    };
+    status.pull_from(&folder, &remote, &[], &accepting).unwrap();
+    assert_eq!(count_events(&folder.join("events"))["a"], 0);
+}
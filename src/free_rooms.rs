@@ -0,0 +1,351 @@
+//! Free time slots of every room on every semester day, to find a free room right now.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context as _;
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+
+use crate::generate_ics::days_of;
+use crate::location::parse_location;
+use crate::output_files::{self, write_atomic};
+use crate::resolved_events::{ResolvedEvent, all_eventfile_events_in};
+use crate::{events, room_calendars};
+
+pub const FILENAME: &str = "free.json";
+
+/// Rooms are only considered free within these times
+const DAY_START: NaiveTime = NaiveTime::from_hms_opt(7, 0, 0).unwrap();
+const DAY_END: NaiveTime = NaiveTime::from_hms_opt(21, 0, 0).unwrap();
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FreeIndex {
+    pub day_start: NaiveTime,
+    pub day_end: NaiveTime,
+    /// Free slots of every room on every day with at least one event in any room
+    pub days: BTreeMap<NaiveDate, BTreeMap<String, Vec<Slot>>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Slot {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct FreeRoom {
+    pub room: String,
+    pub until: NaiveTime,
+}
+
+pub fn build_all() -> anyhow::Result<()> {
+    let index = compute(&all_eventfile_events_in(
+        &Path::new(events::FOLDER).join("events"),
+    ));
+    let folder = Path::new(output_files::FOLDER).join(room_calendars::SUBFOLDER);
+    fs::create_dir_all(&folder).context("failed to create rooms folder")?;
+    write_atomic(
+        &folder.join(FILENAME),
+        serde_json::to_string(&index)?.as_bytes(),
+    )
+    .context("failed to write free rooms index")?;
+    Ok(())
+}
+
+/// Query the index written by [`build_all`]
+pub fn query(
+    building: Option<&str>,
+    at: NaiveDateTime,
+    minutes: u32,
+) -> anyhow::Result<Vec<FreeRoom>> {
+    let path = Path::new(output_files::FOLDER)
+        .join(room_calendars::SUBFOLDER)
+        .join(FILENAME);
+    let content = fs::read_to_string(path).context("failed to read free rooms index")?;
+    let index: FreeIndex =
+        serde_json::from_str(&content).context("failed to parse free rooms index")?;
+    Ok(index.free_rooms(building, at, minutes))
+}
+
+pub fn compute(events: &[ResolvedEvent]) -> FreeIndex {
+    // Name of every room by its key, the first spelling wins
+    let mut rooms = BTreeMap::new();
+    let mut busy: BTreeMap<NaiveDate, BTreeMap<String, Vec<Slot>>> = BTreeMap::new();
+    for resolved in events {
+        let event = &resolved.event;
+        let locations = parse_location(&event.location).rooms;
+        // Multi day events occupy their rooms on every day, all day events the whole day
+        for day in days_of(event) {
+            let start = if event.all_day || day > event.start_time.date() {
+                DAY_START
+            } else {
+                event.start_time.time()
+            };
+            let end = if event.all_day || day < event.end_time.date() {
+                DAY_END
+            } else {
+                event.end_time.time()
+            };
+            let slot = Slot { start, end };
+            // Every day with an event is a semester day, even when the event has no room
+            let day_busy = busy.entry(day).or_default();
+            for room in &locations {
+                let key = room.key();
+                day_busy.entry(key.clone()).or_default().push(slot);
+                rooms.entry(key).or_insert_with(|| room.name());
+            }
+        }
+    }
+
+    let days = busy
+        .into_iter()
+        .map(|(day, mut day_busy)| {
+            let free = rooms
+                .iter()
+                .map(|(key, name)| {
+                    let slots = free_slots(day_busy.remove(key).unwrap_or_default());
+                    (name.clone(), slots)
+                })
+                .collect();
+            (day, free)
+        })
+        .collect();
+
+    FreeIndex {
+        day_start: DAY_START,
+        day_end: DAY_END,
+        days,
+    }
+}
+
+/// Gaps between the busy slots within the day. Busy slots may overlap.
+fn free_slots(mut busy: Vec<Slot>) -> Vec<Slot> {
+    busy.sort_by_key(|slot| slot.start);
+    let mut free = Vec::new();
+    let mut free_since = DAY_START;
+    for slot in busy {
+        if slot.start > free_since {
+            free.push(Slot {
+                start: free_since,
+                end: slot.start.min(DAY_END),
+            });
+        }
+        free_since = free_since.max(slot.end);
+        if free_since >= DAY_END {
+            return free;
+        }
+    }
+    if free_since < DAY_END {
+        free.push(Slot {
+            start: free_since,
+            end: DAY_END,
+        });
+    }
+    free
+}
+
+impl FreeIndex {
+    /// Rooms free at the given time for at least the given minutes.
+    ///
+    /// The building is compared case insensitive like `bt7` matches `BT7 1.060`.
+    pub fn free_rooms(
+        &self,
+        building: Option<&str>,
+        at: NaiveDateTime,
+        minutes: u32,
+    ) -> Vec<FreeRoom> {
+        let Some(rooms) = self.days.get(&at.date()) else {
+            return Vec::new();
+        };
+        let time = at.time();
+        rooms
+            .iter()
+            .filter(|(room, _)| {
+                building.is_none_or(|building| {
+                    parse_location(room).rooms.iter().any(|room| {
+                        room.building
+                            .as_ref()
+                            .is_some_and(|name| name.eq_ignore_ascii_case(building))
+                    })
+                })
+            })
+            .filter_map(|(room, slots)| {
+                let slot = slots
+                    .iter()
+                    .find(|slot| slot.start <= time && time < slot.end)?;
+                let free_minutes = (slot.end - time).num_minutes();
+                (free_minutes >= i64::from(minutes)).then(|| FreeRoom {
+                    room: room.clone(),
+                    until: slot.end,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+const fn time(hour: u32, minute: u32) -> NaiveTime {
+    NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+}
+
+#[cfg(test)]
+fn create_event(day: u32, start: NaiveTime, end: NaiveTime, location: &str) -> ResolvedEvent {
+    let date = NaiveDate::from_ymd_opt(2022, 1, day).unwrap();
+    let minutes = (end - start).num_minutes();
+    let mut resolved = crate::resolved_events::test_event("BTI1-TI", date.and_time(start), minutes);
+    location.clone_into(&mut resolved.event.location);
+    resolved
+}
+
+#[test]
+fn free_slots_with_overlapping_events() {
+    let busy = vec![
+        Slot {
+            start: time(10, 0),
+            end: time(12, 0),
+        },
+        Slot {
+            start: time(8, 0),
+            end: time(9, 0),
+        },
+        Slot {
+            start: time(11, 0),
+            end: time(13, 0),
+        },
+    ];
+    assert_eq!(
+        free_slots(busy),
+        [
+            Slot {
+                start: time(7, 0),
+                end: time(8, 0),
+            },
+            Slot {
+                start: time(9, 0),
+                end: time(10, 0),
+            },
+            Slot {
+                start: time(13, 0),
+                end: time(21, 0),
+            },
+        ]
+    );
+}
+
+#[test]
+fn free_slots_edges() {
+    assert_eq!(
+        free_slots(Vec::new()),
+        [Slot {
+            start: time(7, 0),
+            end: time(21, 0),
+        }]
+    );
+    let all_day = vec![Slot {
+        start: time(6, 0),
+        end: time(22, 0),
+    }];
+    assert!(free_slots(all_day).is_empty());
+}
+
+#[test]
+fn multi_room_locations_occupy_every_room() {
+    let events = [
+        create_event(13, time(8, 0), time(10, 0), "BT7 R1.060, BT7 R1.061"),
+        create_event(13, time(9, 0), time(11, 0), "bt7 1.061"),
+        create_event(14, time(8, 0), time(10, 0), "1060"),
+    ];
+    let index = compute(&events);
+    assert_eq!(index.days.len(), 2);
+    let day = &index.days[&NaiveDate::from_ymd_opt(2022, 1, 13).unwrap()];
+    assert_eq!(
+        day.len(),
+        3,
+        "every known room on every day, same room in every spelling"
+    );
+    assert_eq!(
+        day["BT7 1.061"],
+        [
+            Slot {
+                start: time(7, 0),
+                end: time(8, 0),
+            },
+            Slot {
+                start: time(11, 0),
+                end: time(21, 0),
+            },
+        ]
+    );
+    assert_eq!(
+        day["1060"],
+        [Slot {
+            start: time(7, 0),
+            end: time(21, 0),
+        }]
+    );
+}
+
+#[test]
+fn multi_day_events_occupy_every_day() {
+    let mut block = create_event(13, time(14, 0), time(10, 0), "1060");
+    block.event.end_time = block.event.end_time + chrono::Days::new(2);
+    let mut exam = create_event(17, time(0, 0), time(0, 0), "1061");
+    exam.event.end_time = exam.event.end_time + chrono::Days::new(1);
+    exam.event.all_day = true;
+    let index = compute(&[block, exam]);
+    let on = |day| &index.days[&NaiveDate::from_ymd_opt(2022, 1, day).unwrap()];
+    assert_eq!(
+        on(13)["1060"],
+        [Slot {
+            start: time(7, 0),
+            end: time(14, 0),
+        }]
+    );
+    assert!(on(14)["1060"].is_empty());
+    assert_eq!(
+        on(15)["1060"],
+        [Slot {
+            start: time(10, 0),
+            end: time(21, 0),
+        }]
+    );
+    assert!(on(17)["1061"].is_empty());
+    assert_eq!(index.days.len(), 4);
+}
+
+#[test]
+fn query_free_rooms() {
+    let events = [
+        create_event(13, time(8, 0), time(10, 0), "BT7 R1.060"),
+        create_event(13, time(9, 0), time(11, 0), "BT7 R1.061"),
+        create_event(13, time(10, 30), time(12, 0), "1060"),
+    ];
+    let index = compute(&events);
+    let at = NaiveDate::from_ymd_opt(2022, 1, 13)
+        .unwrap()
+        .and_time(time(10, 0));
+
+    assert_eq!(
+        index.free_rooms(None, at, 0),
+        [
+            FreeRoom {
+                room: "1060".to_owned(),
+                until: time(10, 30),
+            },
+            FreeRoom {
+                room: "BT7 1.060".to_owned(),
+                until: time(21, 0),
+            },
+        ]
+    );
+    assert_eq!(index.free_rooms(Some("bt7"), at, 0).len(), 1);
+    assert!(index.free_rooms(Some("BT"), at, 0).is_empty());
+    assert_eq!(index.free_rooms(None, at, 60).len(), 1);
+    assert!(
+        index
+            .free_rooms(None, at + chrono::Days::new(1), 0)
+            .is_empty()
+    );
+}
@@ -0,0 +1,294 @@
+//! Agenda of a day or week as chat message, like the bots `/today`.
+
+#![expect(clippy::non_ascii_literal)]
+
+use std::fmt::Write as _;
+
+use chrono::{Datelike as _, IsoWeek, NaiveDate};
+
+use crate::generate_ics::{EventStatus, days_of};
+use crate::resolved_events::{ResolvedEvent, resolve_events};
+use crate::userconfigs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Day(NaiveDate),
+    Week(IsoWeek),
+}
+
+impl Period {
+    fn contains(self, date: NaiveDate) -> bool {
+        match self {
+            Self::Day(day) => date == day,
+            Self::Week(week) => date.iso_week() == week,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    /// Telegram `MarkdownV2`
+    Markdown,
+}
+
+const WEEKDAY_NAMES: [&str; 7] = ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"];
+
+/// Agenda of the given chat
+pub fn agenda_of_chat(chat_id: i64, period: Period, format: Format) -> anyhow::Result<String> {
+    let content = userconfigs::load_chat(chat_id)?;
+    let events = resolve_events(content.config);
+    Ok(generate_agenda(&events, period, format))
+}
+
+pub fn generate_agenda(events: &[ResolvedEvent], period: Period, format: Format) -> String {
+    let mut result = String::new();
+    if let Period::Week(week) = period {
+        _ = writeln!(result, "{}\n", bold(format, &format!("KW {}", week.week())));
+    }
+
+    // Events spanning multiple days are listed on every one of them, all day events first
+    let mut entries = events
+        .iter()
+        .flat_map(|resolved| {
+            days_of(&resolved.event)
+                .filter(|date| period.contains(*date))
+                .map(move |date| (date, resolved))
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(date, resolved)| (*date, !resolved.event.all_day));
+
+    let mut last_date = None;
+    for (date, resolved) in entries {
+        if last_date != Some(date) {
+            if last_date.is_some() {
+                result += "\n";
+            }
+            let heading = format!(
+                "{} {}",
+                WEEKDAY_NAMES[date.weekday().num_days_from_monday() as usize],
+                date.format("%d.%m.%Y")
+            );
+            _ = writeln!(result, "{}", bold(format, &heading));
+            last_date = Some(date);
+        }
+        event_line(&mut result, resolved, format);
+    }
+
+    if last_date.is_none() {
+        _ = writeln!(result, "{}", text(format, "Keine Termine."));
+    }
+    result
+}
+
+fn event_line(output: &mut String, resolved: &ResolvedEvent, format: Format) {
+    let event = &resolved.event;
+    let cancelled = event.status == EventStatus::Cancelled;
+
+    let time = if event.all_day {
+        "ganztägig".to_owned()
+    } else if days_of(event).nth(1).is_some() {
+        format!(
+            "{} - {}",
+            event.start_time.format("%d.%m. %H:%M"),
+            event.end_time.format("%d.%m. %H:%M")
+        )
+    } else {
+        format!(
+            "{} - {}",
+            event.start_time.format("%H:%M"),
+            event.end_time.format("%H:%M")
+        )
+    };
+    *output += &text(format, &time);
+    *output += " ";
+    match (format, cancelled) {
+        (Format::Markdown, true) => _ = write!(output, "~{}~", escape_markdown(&event.name)),
+        (Format::Markdown, false) => _ = write!(output, "*{}*", escape_markdown(&event.name)),
+        (Format::Text, _) => *output += &event.name,
+    }
+    if !event.location.is_empty() {
+        let room = format!("📍{}", event.location);
+        *output += " ";
+        *output += &if resolved.room_changed {
+            bold(format, &room)
+        } else {
+            text(format, &room)
+        };
+    }
+    if cancelled {
+        *output += &text(format, " 🚫 fällt aus");
+    } else if resolved.changed {
+        *output += &text(format, " ✏️ geändert");
+    }
+    *output += "\n";
+
+    for line in event
+        .description
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+    {
+        match format {
+            Format::Text => _ = writeln!(output, "    {line}"),
+            Format::Markdown => _ = writeln!(output, ">{}", escape_markdown(line)),
+        }
+    }
+}
+
+fn text(format: Format, value: &str) -> String {
+    match format {
+        Format::Text => value.to_owned(),
+        Format::Markdown => escape_markdown(value),
+    }
+}
+
+fn bold(format: Format, value: &str) -> String {
+    match format {
+        Format::Text => value.to_owned(),
+        Format::Markdown => format!("*{}*", escape_markdown(value)),
+    }
+}
+
+/// Escape the characters with a meaning in Telegram `MarkdownV2`
+fn escape_markdown(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for char in value.chars() {
+        if matches!(
+            char,
+            '\\' | '_'
+                | '*'
+                | '['
+                | ']'
+                | '('
+                | ')'
+                | '~'
+                | '`'
+                | '>'
+                | '#'
+                | '+'
+                | '-'
+                | '='
+                | '|'
+                | '{'
+                | '}'
+                | '.'
+                | '!'
+        ) {
+            result.push('\\');
+        }
+        result.push(char);
+    }
+    result
+}
+
+#[cfg(test)]
+fn create_events() -> Vec<ResolvedEvent> {
+    let event = |day: u32, hour: u32, name: &str| {
+        let start_time = NaiveDate::from_ymd_opt(2020, 4, day)
+            .unwrap()
+            .and_hms_opt(hour, 15, 0)
+            .unwrap();
+        crate::resolved_events::test_event(name, start_time, 180)
+    };
+
+    let mut cancelled = event(7, 8, "BTI5-VS");
+    cancelled.event.status = EventStatus::Cancelled;
+    cancelled.changed = true;
+    let mut moved = event(7, 14, "BTI5-VSP/01");
+    moved.changed = true;
+    moved.room_changed = true;
+    moved.event.location = "1061".to_owned();
+    moved.event.description = "Dozent: HTM\n\nLaptop mitbringen!".to_owned();
+
+    vec![
+        event(6, 8, "BTI5-VS"),
+        cancelled,
+        moved,
+        event(14, 8, "BTI5-VS"),
+    ]
+}
+
+#[test]
+fn text_of_day() {
+    let day = NaiveDate::from_ymd_opt(2020, 4, 7).unwrap();
+    let result = generate_agenda(&create_events(), Period::Day(day), Format::Text);
+    assert_eq!(
+        result,
+        "Di 07.04.2020
+08:15 - 11:15 BTI5-VS 📍1060 🚫 fällt aus
+14:15 - 17:15 BTI5-VSP/01 📍1061 ✏️ geändert
+    Dozent: HTM
+    Laptop mitbringen!
+"
+    );
+}
+
+#[test]
+fn markdown_of_week() {
+    let week = NaiveDate::from_ymd_opt(2020, 4, 6).unwrap().iso_week();
+    let result = generate_agenda(&create_events(), Period::Week(week), Format::Markdown);
+    assert_eq!(
+        result,
+        r"*KW 15*
+
+*Mo 06\.04\.2020*
+08:15 \- 11:15 *BTI5\-VS* 📍1060
+
+*Di 07\.04\.2020*
+08:15 \- 11:15 ~BTI5\-VS~ 📍1060 🚫 fällt aus
+14:15 \- 17:15 *BTI5\-VSP/01* *📍1061* ✏️ geändert
+>Dozent: HTM
+>Laptop mitbringen\!
+"
+    );
+}
+
+#[test]
+fn multi_day_events_are_listed_every_day() {
+    let mut events = create_events();
+    let mut excursion = events[0].clone();
+    excursion.event.name = "Exkursion".to_owned();
+    excursion.event.start_time = NaiveDate::from_ymd_opt(2020, 4, 6)
+        .unwrap()
+        .and_hms_opt(0, 0, 0)
+        .unwrap();
+    excursion.event.end_time = excursion.event.start_time + chrono::Days::new(2);
+    excursion.event.all_day = true;
+    excursion.event.location = String::new();
+    let mut trip = events[0].clone();
+    trip.event.name = "Messe".to_owned();
+    trip.event.end_time = trip.event.start_time + chrono::Days::new(1);
+    trip.event.location = String::new();
+    events.extend([excursion, trip]);
+    events.sort_by_key(|resolved| resolved.event.start_time);
+
+    let week = NaiveDate::from_ymd_opt(2020, 4, 6).unwrap().iso_week();
+    let result = generate_agenda(&events, Period::Week(week), Format::Text);
+    assert_eq!(
+        result,
+        "KW 15
+
+Mo 06.04.2020
+ganztägig Exkursion
+08:15 - 11:15 BTI5-VS 📍1060
+06.04. 08:15 - 07.04. 08:15 Messe
+
+Di 07.04.2020
+ganztägig Exkursion
+06.04. 08:15 - 07.04. 08:15 Messe
+08:15 - 11:15 BTI5-VS 📍1060 🚫 fällt aus
+14:15 - 17:15 BTI5-VSP/01 📍1061 ✏️ geändert
+    Dozent: HTM
+    Laptop mitbringen!
+"
+    );
+}
+
+#[test]
+fn empty_period() {
+    let day = NaiveDate::from_ymd_opt(2020, 4, 8).unwrap();
+    assert_eq!(
+        generate_agenda(&create_events(), Period::Day(day), Format::Markdown),
+        "Keine Termine\\.\n"
+    );
+}
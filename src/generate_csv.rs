@@ -0,0 +1,134 @@
+//! Spreadsheet friendly export of resolved events as CSV (RFC 4180).
+
+use std::borrow::Cow;
+
+use crate::generate_ics::{EventStatus, all_day_end};
+use crate::resolved_events::{ResolvedEvent, resolve_events};
+use crate::userconfigs;
+
+/// Stable column order. New columns are only appended.
+const COLUMNS: [&str; 8] = [
+    "start",
+    "end",
+    "name",
+    "location",
+    "description",
+    "status",
+    "eventfile",
+    "changed",
+];
+
+/// Export of the given chat
+pub fn csv_of_chat(chat_id: i64) -> anyhow::Result<String> {
+    let content = userconfigs::load_chat(chat_id)?;
+    Ok(generate_csv(&resolve_events(content.config)))
+}
+
+pub fn generate_csv(events: &[ResolvedEvent]) -> String {
+    let mut result = String::new();
+    push_record(&mut result, &COLUMNS);
+    for resolved in events {
+        let event = &resolved.event;
+        // All day events have dates without time, the end is their last day
+        let (start, end) = if event.all_day {
+            (
+                event.start_time.format("%Y-%m-%d").to_string(),
+                all_day_end(event)
+                    .pred_opt()
+                    .map_or_else(String::new, |last| last.format("%Y-%m-%d").to_string()),
+            )
+        } else {
+            (
+                event.start_time.format("%Y-%m-%d %H:%M").to_string(),
+                event.end_time.format("%Y-%m-%d %H:%M").to_string(),
+            )
+        };
+        push_record(
+            &mut result,
+            &[
+                &start,
+                &end,
+                &event.name,
+                &event.location,
+                &event.description,
+                match event.status {
+                    EventStatus::Confirmed => "confirmed",
+                    EventStatus::Cancelled => "cancelled",
+                    EventStatus::Tentative => "tentative",
+                },
+                &resolved.eventfile,
+                if resolved.changed { "true" } else { "false" },
+            ],
+        );
+    }
+    result
+}
+
+fn push_record(output: &mut String, fields: &[&str]) {
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            output.push(',');
+        }
+        *output += &quote(field);
+    }
+    *output += "\r\n";
+}
+
+/// Fields containing separators, quotes or line breaks are quoted, quotes within are doubled
+fn quote(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[cfg(test)]
+fn create_event(name: &str, description: &str) -> ResolvedEvent {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap();
+    let mut resolved = crate::resolved_events::test_event("BTI5-VS", start_time, 180);
+    name.clone_into(&mut resolved.event.name);
+    description.clone_into(&mut resolved.event.description);
+    resolved
+}
+
+#[test]
+fn quote_examples() {
+    assert_eq!(quote("BTI5-VS"), "BTI5-VS");
+    assert_eq!(quote(""), "");
+    assert_eq!(quote("1060, 1061"), "\"1060, 1061\"");
+    assert_eq!(quote("say \"hi\""), "\"say \"\"hi\"\"\"");
+    assert_eq!(quote("multi\nline"), "\"multi\nline\"");
+}
+
+#[test]
+fn csv_example() {
+    let mut cancelled = create_event("BTI5-VS", "Dozent: HTM\n\nnotes, \"quoted\"");
+    cancelled.event.status = EventStatus::Cancelled;
+    cancelled.changed = true;
+    let events = [create_event("BTI5-VSP/01", ""), cancelled];
+    assert_eq!(
+        generate_csv(&events),
+        "start,end,name,location,description,status,eventfile,changed\r
+2020-04-02 08:15,2020-04-02 11:15,BTI5-VSP/01,1060,,confirmed,BTI5-VS,false\r
+2020-04-02 08:15,2020-04-02 11:15,BTI5-VS,1060,\"Dozent: HTM\n\nnotes, \"\"quoted\"\"\",cancelled,BTI5-VS,true\r
+"
+    );
+}
+
+#[test]
+fn all_day_events_have_dates_only() {
+    let mut event = create_event("Prüfungszeitraum", "");
+    event.event.start_time = event.event.start_time.date().into();
+    event.event.end_time = event.event.start_time + chrono::Days::new(3);
+    event.event.all_day = true;
+    assert_eq!(
+        generate_csv(&[event]),
+        "start,end,name,location,description,status,eventfile,changed\r
+2020-04-02,2020-04-04,Prüfungszeitraum,1060,,confirmed,BTI5-VS,false\r
+"
+    );
+}
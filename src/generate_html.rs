@@ -0,0 +1,255 @@
+//! Printable weekly timetable ("Stundenplan") of the resolved events of a user.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+use chrono::{Datelike as _, IsoWeek, NaiveDate, Weekday};
+use quick_xml::escape::escape;
+
+use crate::generate_ics::EventStatus;
+use crate::resolved_events::ResolvedEvent;
+use crate::timetable;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:1em}
+section{break-inside:avoid;page-break-inside:avoid;margin-bottom:2em}
+.grid{display:grid;grid-template-columns:3em repeat(var(--days),1fr);gap:0 2px}
+.grid>h3{margin:0;padding:.2em;font-size:1em;text-align:center;border-bottom:1px solid #888}
+.hours,.day{position:relative;height:calc(var(--hours) * 3.5em);background:repeating-linear-gradient(#fff 0 3.45em,#ddd 3.45em 3.5em)}
+.hours span{position:absolute;font-size:.8em}
+.event{position:absolute;box-sizing:border-box;overflow:hidden;padding:.2em;font-size:.8em;background:#d6e6f5;border-left:3px solid #1e64aa}
+.event time,.event .room{display:block}
+.cancelled{text-decoration:line-through;opacity:.6}
+.room-changed{background:#ffe066;font-weight:bold}
+.all-day{list-style:none;padding:0}
+.all-day time{font-size:.8em}
+";
+
+const WEEKDAY_NAMES: [&str; 7] = ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"];
+
+pub fn generate_html(calendarname: &str, events: &[ResolvedEvent]) -> String {
+    let title = escape(calendarname);
+    let mut result = String::new();
+    result += "<!DOCTYPE html>\n<html lang=\"de\">\n<head>\n<meta charset=\"utf-8\">\n";
+    _ = writeln!(result, "<title>Stundenplan {title}</title>");
+    _ = writeln!(result, "<style>\n{STYLE}</style>\n</head>\n<body>");
+    _ = writeln!(result, "<h1>Stundenplan {title}</h1>");
+
+    let mut weeks: BTreeMap<IsoWeek, Vec<&ResolvedEvent>> = BTreeMap::new();
+    for resolved in events {
+        weeks
+            .entry(resolved.event.start_time.iso_week())
+            .or_default()
+            .push(resolved);
+    }
+    for (week, events) in weeks {
+        week_as_html(&mut result, week, &events);
+    }
+
+    result += "</body>\n</html>\n";
+    result
+}
+
+fn week_as_html(output: &mut String, week: IsoWeek, events: &[&ResolvedEvent]) {
+    let monday = NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon)
+        .expect("iso week of an event should exist");
+    let days = timetable::shown_days(events);
+    let (events, all_day_events) = timetable::split_all_day(events);
+    let (first_hour, last_hour) = timetable::hour_range(&events);
+
+    _ = writeln!(
+        output,
+        "<section>\n<h2>KW {} ({} &ndash; {})</h2>",
+        week.week(),
+        monday.format("%d.%m.%Y"),
+        (monday + chrono::Days::new(6)).format("%d.%m.%Y"),
+    );
+    if !all_day_events.is_empty() {
+        *output += "<ul class=\"all-day\">";
+        for resolved in all_day_events {
+            _ = write!(
+                output,
+                "<li{}><time>{}</time> <strong>{}</strong></li>",
+                if resolved.event.status == EventStatus::Cancelled {
+                    " class=\"cancelled\""
+                } else {
+                    ""
+                },
+                timetable::day_range(resolved),
+                escape(&resolved.event.name),
+            );
+        }
+        *output += "</ul>\n";
+    }
+    _ = writeln!(
+        output,
+        "<div class=\"grid\" style=\"--days:{days};--hours:{}\">",
+        last_hour - first_hour
+    );
+
+    *output += "<h3></h3>";
+    for (offset, name) in (0..days).zip(WEEKDAY_NAMES) {
+        let date = monday + chrono::Days::new(offset.into());
+        _ = write!(output, "<h3>{name} {}</h3>", date.format("%d.%m."));
+    }
+    *output += "\n<div class=\"hours\">";
+    for hour in first_hour..last_hour {
+        _ = write!(
+            output,
+            "<span style=\"top:{}\">{hour:02}:00</span>",
+            percent(hour - first_hour, last_hour - first_hour)
+        );
+    }
+    *output += "</div>\n";
+
+    for offset in 0..days {
+        let date = monday + chrono::Days::new(offset.into());
+        let day_events = events
+            .iter()
+            .filter(|resolved| resolved.event.start_time.date() == date)
+            .copied()
+            .collect::<Vec<_>>();
+        day_as_html(output, &day_events, first_hour, last_hour);
+    }
+
+    *output += "</div>\n</section>\n";
+}
+
+fn day_as_html(output: &mut String, events: &[&ResolvedEvent], first_hour: u32, last_hour: u32) {
+    let (lanes, lane_amount) = timetable::lanes(events);
+
+    *output += "<div class=\"day\">\n";
+    let total_minutes = (last_hour - first_hour) * 60;
+    for (resolved, lane) in events.iter().zip(lanes) {
+        let event = &resolved.event;
+        let (start_minutes, end_minutes) =
+            timetable::minutes_within(resolved, first_hour, last_hour);
+
+        let mut classes = String::from("event");
+        if event.status == EventStatus::Cancelled {
+            classes += " cancelled";
+        }
+        if resolved.changed {
+            classes += " changed";
+        }
+        _ = write!(
+            output,
+            "<div class=\"{classes}\" style=\"top:{};height:{};left:{};width:{}\"",
+            percent(start_minutes, total_minutes),
+            percent(end_minutes - start_minutes, total_minutes),
+            percent(lane, lane_amount),
+            percent(1, lane_amount),
+        );
+        if !event.description.is_empty() {
+            _ = write!(output, " title=\"{}\"", escape(&event.description));
+        }
+        _ = write!(
+            output,
+            "><time>{} &ndash; {}</time><strong>{}</strong>",
+            event.start_time.format("%H:%M"),
+            event.end_time.format("%H:%M"),
+            escape(&event.name),
+        );
+        if !event.location.is_empty() {
+            _ = write!(
+                output,
+                "<span class=\"room{}\">{}</span>",
+                if resolved.room_changed {
+                    " room-changed"
+                } else {
+                    ""
+                },
+                escape(&event.location),
+            );
+        }
+        *output += "</div>\n";
+    }
+    *output += "</div>\n";
+}
+
+fn percent(part: u32, total: u32) -> String {
+    format!("{:.2}%", f64::from(part) * 100.0 / f64::from(total))
+}
+
+#[cfg(test)]
+fn create_event(day: u32, hour: u32, name: &str) -> ResolvedEvent {
+    let start_time = NaiveDate::from_ymd_opt(2020, 4, day)
+        .unwrap()
+        .and_hms_opt(hour, 15, 0)
+        .unwrap();
+    let mut resolved = crate::resolved_events::test_event("BTI5-VSP", start_time, 180);
+    name.clone_into(&mut resolved.event.name);
+    resolved
+}
+
+#[test]
+fn one_section_per_week() {
+    let events = [
+        create_event(2, 8, "first"),
+        create_event(9, 8, "second"),
+        create_event(10, 14, "third"),
+    ];
+    let html = generate_html("Peter", &events);
+    assert_eq!(html.matches("<section>").count(), 2);
+    assert!(html.contains("<h2>KW 14 (30.03.2020 &ndash; 05.04.2020)</h2>"));
+    assert!(html.contains("<h2>KW 15 (06.04.2020 &ndash; 12.04.2020)</h2>"));
+    assert!(html.contains("<h3>Do 09.04.</h3>"));
+    assert!(!html.contains("<h3>Sa"), "no weekend without events there");
+}
+
+#[test]
+fn event_is_positioned_within_the_hours_of_the_week() {
+    let events = [create_event(2, 8, "early"), create_event(2, 14, "late")];
+    let html = generate_html("Peter", &events);
+    // 08:00 until 18:00
+    assert!(html.contains("--hours:10"), "{html}");
+    assert!(
+        html.contains("top:2.50%;height:30.00%;left:0.00%;width:100.00%"),
+        "{html}"
+    );
+}
+
+#[test]
+fn overlapping_events_are_side_by_side() {
+    let events = [create_event(2, 8, "first"), create_event(2, 9, "second")];
+    let html = generate_html("Peter", &events);
+    assert!(html.contains("left:0.00%;width:50.00%"), "{html}");
+    assert!(html.contains("left:50.00%;width:50.00%"), "{html}");
+}
+
+#[test]
+fn all_day_events_are_listed_above_the_hours() {
+    let mut all_day = create_event(2, 0, "Prüfungszeitraum");
+    all_day.event.start_time = all_day.event.start_time.date().into();
+    all_day.event.end_time = all_day.event.start_time + chrono::Days::new(2);
+    all_day.event.all_day = true;
+    let html = generate_html("Peter", &[all_day, create_event(2, 8, "timed")]);
+    assert!(
+        html.contains("<li><time>02.04. - 03.04.</time> <strong>Prüfungszeitraum</strong></li>"),
+        "{html}"
+    );
+    assert_eq!(html.matches("<div class=\"event").count(), 1, "{html}");
+    // 08:00 until 12:00, not from midnight
+    assert!(html.contains("--hours:4"), "{html}");
+}
+
+#[test]
+fn cancelled_and_room_changes_are_marked() {
+    let mut cancelled = create_event(2, 8, "cancelled <b>");
+    cancelled.event.status = EventStatus::Cancelled;
+    cancelled.changed = true;
+    let mut moved = create_event(3, 8, "moved");
+    moved.changed = true;
+    moved.room_changed = true;
+    moved.event.location = "Audimax & more".to_owned();
+
+    let html = generate_html("Peter", &[cancelled, moved]);
+    assert!(html.contains("class=\"event cancelled changed\""), "{html}");
+    assert!(
+        html.contains("<strong>cancelled &lt;b&gt;</strong>"),
+        "{html}"
+    );
+    assert!(
+        html.contains("<span class=\"room room-changed\">Audimax &amp; more</span>"),
+        "{html}"
+    );
+}
@@ -1,16 +1,70 @@
+#![expect(clippy::non_ascii_literal)]
+
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::Write as _;
-use std::hash::{Hash, Hasher as _};
+use std::hash::{Hash, Hasher};
+
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime};
 
-use chrono::NaiveDateTime;
+use crate::location::Buildings;
+use crate::{location, meeting};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum EventStatus {
     Confirmed,
     Cancelled,
+    /// Optional like voluntary exercises
+    Tentative,
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+impl EventStatus {
+    pub const fn as_ics(self) -> &'static str {
+        match self {
+            Self::Confirmed => "CONFIRMED",
+            Self::Cancelled => "CANCELLED",
+            Self::Tentative => "TENTATIVE",
+        }
+    }
+}
+
+/// Kind of event as given by the eventfiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventType {
+    Lecture,
+    Lab,
+    Exam,
+    Tutorial,
+    /// Types unknown to this version
+    #[serde(other)]
+    Other,
+}
+
+impl EventType {
+    const fn category(self) -> Option<&'static str> {
+        match self {
+            Self::Lecture => Some("Vorlesung"),
+            Self::Lab => Some("Praktikum"),
+            Self::Exam => Some("Prüfung"),
+            Self::Tutorial => Some("Tutorium"),
+            Self::Other => None,
+        }
+    }
+}
+
+/// Optional information of the eventfiles
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventMeta {
+    pub event_type: Option<EventType>,
+    pub url: Option<String>,
+    pub categories: Vec<String>,
+    /// Group within the module like `BTI1-TI/01`
+    pub group: Option<String>,
+    /// Id of the event in the source of the eventfiles
+    pub original_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SoonToBeIcsEvent {
     pub name: String,
     pub status: EventStatus,
@@ -19,6 +73,52 @@ pub struct SoonToBeIcsEvent {
     pub alert_minutes_before: Option<u16>,
     pub description: String,
     pub location: String,
+    /// Abbreviations like `HTM`
+    pub lecturers: Vec<String>,
+    /// Whole days from the date of the start until the end
+    pub all_day: bool,
+    /// Shown as free time in free/busy
+    pub transparent: bool,
+    pub meta: EventMeta,
+}
+
+/// Only the fields the UID was always built from are hashed.
+/// Adding fields would change the UID of every event.
+/// Tentative events are hashed as confirmed, marking an event optional keeps its UID.
+impl Hash for SoonToBeIcsEvent {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        match self.status {
+            EventStatus::Tentative => EventStatus::Confirmed,
+            status => status,
+        }
+        .hash(state);
+        self.start_time.hash(state);
+        self.end_time.hash(state);
+        self.alert_minutes_before.hash(state);
+        self.description.hash(state);
+        self.location.hash(state);
+    }
+}
+
+/// Tests only spell out the fields they care about
+#[cfg(test)]
+impl Default for SoonToBeIcsEvent {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            status: EventStatus::Confirmed,
+            start_time: NaiveDateTime::default(),
+            end_time: NaiveDateTime::default(),
+            alert_minutes_before: None,
+            description: String::new(),
+            location: String::new(),
+            lecturers: Vec::new(),
+            all_day: false,
+            transparent: false,
+            meta: EventMeta::default(),
+        }
+    }
 }
 
 const ICS_PREFIX: &str = "BEGIN:VCALENDAR
@@ -46,9 +146,21 @@ END:STANDARD
 END:VTIMEZONE
 ";
 
+/// Linked from events without an own link
+pub const BOT_URL: &str = "https://telegram.me/HAWHHCalendarBot";
+
 const ICS_SUFFIX: &str = "END:VCALENDAR\n";
 
 pub fn generate_ics(calendarname: &str, events: &[SoonToBeIcsEvent]) -> String {
+    generate_ics_with_buildings(calendarname, events, location::buildings())
+}
+
+/// [`generate_ics`] with the given buildings for the addresses and coordinates of the locations
+pub fn generate_ics_with_buildings(
+    calendarname: &str,
+    events: &[SoonToBeIcsEvent],
+    buildings: &Buildings,
+) -> String {
     let mut result = String::default();
 
     result += ICS_PREFIX;
@@ -56,7 +168,7 @@ pub fn generate_ics(calendarname: &str, events: &[SoonToBeIcsEvent]) -> String {
     result += ICS_TIMEZONE;
 
     for event in events {
-        event_as_ics_vevent_string(&mut result, event);
+        event_as_ics_vevent_string(&mut result, event, buildings);
     }
 
     result += ICS_SUFFIX;
@@ -64,43 +176,61 @@ pub fn generate_ics(calendarname: &str, events: &[SoonToBeIcsEvent]) -> String {
     result.replace('\n', "\r\n")
 }
 
-fn event_as_ics_vevent_string(output: &mut String, event: &SoonToBeIcsEvent) {
+fn event_as_ics_vevent_string(
+    output: &mut String,
+    event: &SoonToBeIcsEvent,
+    buildings: &Buildings,
+) {
     *output += "BEGIN:VEVENT\n";
-    *output += "TRANSP:OPAQUE\n";
-
-    _ = writeln!(
-        output,
-        "STATUS:{}",
-        match event.status {
-            EventStatus::Confirmed => "CONFIRMED",
-            EventStatus::Cancelled => "CANCELLED",
-        }
-        .to_owned()
-    );
+    _ = writeln!(output, "TRANSP:{}", transp(event));
+    _ = writeln!(output, "STATUS:{}", event.status.as_ics());
 
     _ = writeln!(
         output,
         "SUMMARY:{}",
         string_to_ical_escaped_text(&event.name)
     );
-    _ = writeln!(
-        output,
-        "DTSTART;TZID=Europe/Berlin:{}",
-        date_to_ics_date(event.start_time)
-    );
-    _ = writeln!(
-        output,
-        "DTEND;TZID=Europe/Berlin:{}",
-        date_to_ics_date(event.end_time)
-    );
+    if event.all_day {
+        _ = writeln!(
+            output,
+            "DTSTART;VALUE=DATE:{}",
+            event.start_time.format("%Y%m%d")
+        );
+        _ = writeln!(
+            output,
+            "DTEND;VALUE=DATE:{}",
+            all_day_end(event).format("%Y%m%d")
+        );
+    } else {
+        _ = writeln!(
+            output,
+            "DTSTART;TZID=Europe/Berlin:{}",
+            date_to_ics_date(event.start_time)
+        );
+        _ = writeln!(
+            output,
+            "DTEND;TZID=Europe/Berlin:{}",
+            date_to_ics_date(event.end_time)
+        );
+    }
 
-    if !event.location.is_empty() {
+    let meetings = meeting::meetings_of(event);
+    let meeting_url = meetings.first().map(|meeting| meeting.url.as_str());
+    let (location, geo) = location::for_calendar(&event.location, meeting_url, buildings);
+    if !location.is_empty() {
         _ = writeln!(
             output,
             "LOCATION:{}",
-            string_to_ical_escaped_text(&event.location)
+            string_to_ical_escaped_text(&location)
         );
     }
+    if let Some((latitude, longitude)) = geo {
+        _ = writeln!(output, "GEO:{latitude};{longitude}");
+    }
+
+    for lecturer in &event.lecturers {
+        _ = writeln!(output, "CONTACT:{}", string_to_ical_escaped_text(lecturer));
+    }
 
     if !event.description.is_empty() {
         _ = writeln!(
@@ -110,7 +240,30 @@ fn event_as_ics_vevent_string(output: &mut String, event: &SoonToBeIcsEvent) {
         );
     }
 
-    *output += "URL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\n";
+    let categories = categories(event);
+    if !categories.is_empty() {
+        let categories = categories
+            .iter()
+            .map(|category| string_to_ical_escaped_text(category))
+            .collect::<Vec<_>>();
+        _ = writeln!(output, "CATEGORIES:{}", categories.join(","));
+    }
+
+    for meeting in &meetings {
+        _ = writeln!(
+            output,
+            "CONFERENCE;VALUE=URI;FEATURE=VIDEO;LABEL={}:{}",
+            meeting.label, meeting.url
+        );
+    }
+    _ = writeln!(output, "URL;VALUE=URI:{}", url(event, meeting_url));
+    if let Some(original_id) = &event.meta.original_id {
+        _ = writeln!(
+            output,
+            "X-HAWHH-ORIGINAL-ID:{}",
+            string_to_ical_escaped_text(original_id)
+        );
+    }
     _ = writeln!(
         output,
         "UID:{}@calendarbot.hawhh.de",
@@ -124,6 +277,65 @@ fn event_as_ics_vevent_string(output: &mut String, event: &SoonToBeIcsEvent) {
     *output += "END:VEVENT\n";
 }
 
+/// Time transparency, whether the event blocks time in free/busy
+pub const fn transp(event: &SoonToBeIcsEvent) -> &'static str {
+    if event.transparent {
+        "TRANSPARENT"
+    } else {
+        "OPAQUE"
+    }
+}
+
+/// Type, categories and group of the event without duplicates
+pub fn categories(event: &SoonToBeIcsEvent) -> Vec<&str> {
+    let mut result: Vec<&str> = Vec::new();
+    let meta = &event.meta;
+    for category in meta
+        .event_type
+        .and_then(EventType::category)
+        .into_iter()
+        .chain(meta.categories.iter().map(String::as_str))
+        .chain(meta.group.as_deref())
+    {
+        if !category.is_empty() && !result.contains(&category) {
+            result.push(category);
+        }
+    }
+    result
+}
+
+/// Link of the event from the eventfile, the online meeting or the bot
+pub fn url<'event>(
+    event: &'event SoonToBeIcsEvent,
+    meeting_url: Option<&'event str>,
+) -> &'event str {
+    event.meta.url.as_deref().or(meeting_url).unwrap_or(BOT_URL)
+}
+
+/// Exclusive end date of an all day event. An end at midnight does not include that day.
+pub fn all_day_end(event: &SoonToBeIcsEvent) -> NaiveDate {
+    let end = event.end_time;
+    if end.time() == NaiveTime::MIN && end.date() > event.start_time.date() {
+        end.date()
+    } else {
+        end.date() + Days::new(1)
+    }
+}
+
+/// Every day the event takes place on, at least the day of the start
+pub fn days_of(event: &SoonToBeIcsEvent) -> impl Iterator<Item = NaiveDate> {
+    let first = event.start_time.date();
+    let end = if event.all_day {
+        all_day_end(event)
+    } else if event.end_time.time() == NaiveTime::MIN {
+        event.end_time.date()
+    } else {
+        event.end_time.date() + Days::new(1)
+    };
+    let end = end.max(first + Days::new(1));
+    first.iter_days().take_while(move |day| *day < end)
+}
+
 /// escape according to <https://www.kanzaki.com/docs/ical/text.html>
 fn string_to_ical_escaped_text(text: &str) -> String {
     text.replace('\\', "\\\\")
@@ -132,7 +344,7 @@ fn string_to_ical_escaped_text(text: &str) -> String {
         .replace('\n', "\\n")
 }
 
-fn calculate_event_hash(event: &SoonToBeIcsEvent) -> String {
+pub fn calculate_event_hash(event: &SoonToBeIcsEvent) -> String {
     format!("{:x}", calculate_hash(&event))
 }
 
@@ -156,7 +368,7 @@ fn create_valarm(output: &mut String, minutes_before: u16) {
 }
 
 /// <https://www.kanzaki.com/docs/ical/duration-t.html>
-fn minutes_to_ical_duration(minutes_before: u16) -> String {
+pub fn minutes_to_ical_duration(minutes_before: u16) -> String {
     let hours = minutes_before / 60;
     let minutes = minutes_before % 60;
     if hours > 0 && minutes > 0 {
@@ -170,7 +382,7 @@ fn minutes_to_ical_duration(minutes_before: u16) -> String {
 
 #[test]
 fn parse_ics_date() {
-    let date = chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+    let date = NaiveDate::from_ymd_opt(2020, 8, 22)
         .unwrap()
         .and_hms_opt(8, 30, 0)
         .unwrap();
@@ -183,27 +395,159 @@ fn create_minimal_event_vevent() {
     let event = SoonToBeIcsEvent {
         name: "BTI5-VS".to_owned(),
         status: EventStatus::Cancelled,
-        start_time: chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+        start_time: NaiveDate::from_ymd_opt(2020, 8, 22)
             .unwrap()
             .and_hms_opt(8, 30, 0)
             .unwrap(),
-        end_time: chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+        end_time: NaiveDate::from_ymd_opt(2020, 8, 22)
             .unwrap()
             .and_hms_opt(11, 30, 0)
             .unwrap(),
-        alert_minutes_before: None,
-        description: String::new(),
-        location: String::new(),
+        ..SoonToBeIcsEvent::default()
     };
 
     let mut result = String::new();
-    event_as_ics_vevent_string(&mut result, &event);
+    event_as_ics_vevent_string(&mut result, &event, &Buildings::new());
     assert_eq!(
         result,
         "BEGIN:VEVENT\nTRANSP:OPAQUE\nSTATUS:CANCELLED\nSUMMARY:BTI5-VS\nDTSTART;TZID=Europe/Berlin:20200822T083000\nDTEND;TZID=Europe/Berlin:20200822T113000\nURL;VALUE=URI:https://telegram.me/HAWHHCalendarBot\nUID:c2238d8b06ddebc8@calendarbot.hawhh.de\nEND:VEVENT\n"
     );
 }
 
+#[test]
+fn location_is_cleaned_without_changing_uid() {
+    let start_time = NaiveDate::from_ymd_opt(2020, 8, 22)
+        .unwrap()
+        .and_hms_opt(8, 30, 0)
+        .unwrap();
+    let event = SoonToBeIcsEvent {
+        name: "BTI5-VS".to_owned(),
+        start_time,
+        end_time: start_time,
+        location: "Raum  R0.12 / Online".to_owned(),
+        ..SoonToBeIcsEvent::default()
+    };
+
+    let mut result = String::new();
+    event_as_ics_vevent_string(&mut result, &event, &Buildings::new());
+    assert!(result.contains("\nLOCATION:0.12\\, Online\n"), "{result}");
+    assert!(result.contains(&format!("UID:{}@", calculate_event_hash(&event))));
+}
+
+#[test]
+fn meeting_links_are_conferences() {
+    let start_time = NaiveDate::from_ymd_opt(2020, 8, 22)
+        .unwrap()
+        .and_hms_opt(8, 30, 0)
+        .unwrap();
+    let event = SoonToBeIcsEvent {
+        name: "BTI5-VS".to_owned(),
+        start_time,
+        end_time: start_time,
+        description: "Zoom: https://haw-hamburg.zoom.us/j/1".to_owned(),
+        location: "Online".to_owned(),
+        ..SoonToBeIcsEvent::default()
+    };
+
+    let mut result = String::new();
+    event_as_ics_vevent_string(&mut result, &event, &Buildings::new());
+    assert!(
+        result.contains("\nLOCATION:https://haw-hamburg.zoom.us/j/1\n"),
+        "{result}"
+    );
+    assert!(result.contains(
+        "\nCONFERENCE;VALUE=URI;FEATURE=VIDEO;LABEL=Zoom:https://haw-hamburg.zoom.us/j/1\n"
+    ));
+    assert!(result.contains("\nURL;VALUE=URI:https://haw-hamburg.zoom.us/j/1\n"));
+}
+
+#[test]
+fn tentative_transparent_event_keeps_uid() {
+    let start_time = NaiveDate::from_ymd_opt(2020, 8, 22)
+        .unwrap()
+        .and_hms_opt(8, 30, 0)
+        .unwrap();
+    let confirmed = SoonToBeIcsEvent {
+        name: "BTI5-VS Tutorium".to_owned(),
+        start_time,
+        end_time: start_time,
+        ..SoonToBeIcsEvent::default()
+    };
+    let tentative = SoonToBeIcsEvent {
+        status: EventStatus::Tentative,
+        transparent: true,
+        ..confirmed.clone()
+    };
+
+    let mut result = String::new();
+    event_as_ics_vevent_string(&mut result, &tentative, &Buildings::new());
+    assert!(
+        result.starts_with("BEGIN:VEVENT\nTRANSP:TRANSPARENT\nSTATUS:TENTATIVE\n"),
+        "{result}"
+    );
+    assert_eq!(
+        calculate_event_hash(&tentative),
+        calculate_event_hash(&confirmed)
+    );
+}
+
+#[test]
+fn all_day_end_is_exclusive() {
+    let date = |day: u32, hour: u32| {
+        NaiveDate::from_ymd_opt(2022, 2, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    };
+    let event = |start_time: NaiveDateTime, end_time: NaiveDateTime| SoonToBeIcsEvent {
+        name: "Prüfungszeitraum".to_owned(),
+        start_time,
+        end_time,
+        all_day: true,
+        ..SoonToBeIcsEvent::default()
+    };
+    let day = |day: u32| NaiveDate::from_ymd_opt(2022, 2, day).unwrap();
+    assert_eq!(all_day_end(&event(date(1, 0), date(1, 0))), day(2));
+    assert_eq!(all_day_end(&event(date(1, 0), date(2, 0))), day(2));
+    assert_eq!(all_day_end(&event(date(1, 0), date(4, 23))), day(5));
+
+    let mut result = String::new();
+    event_as_ics_vevent_string(
+        &mut result,
+        &event(date(1, 0), date(4, 0)),
+        &Buildings::new(),
+    );
+    assert!(result.contains("\nDTSTART;VALUE=DATE:20220201\nDTEND;VALUE=DATE:20220204\n"));
+}
+
+#[test]
+fn days_of_examples() {
+    let date = |day: u32, hour: u32| {
+        NaiveDate::from_ymd_opt(2022, 2, day)
+            .unwrap()
+            .and_hms_opt(hour, 0, 0)
+            .unwrap()
+    };
+    let days = |start_time: NaiveDateTime, end_time: NaiveDateTime, all_day: bool| {
+        let event = SoonToBeIcsEvent {
+            name: "BTI1-TI".to_owned(),
+            start_time,
+            end_time,
+            all_day,
+            ..SoonToBeIcsEvent::default()
+        };
+        days_of(&event)
+            .map(|day| chrono::Datelike::day(&day))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(days(date(1, 8), date(1, 10), false), [1]);
+    assert_eq!(days(date(1, 8), date(3, 10), false), [1, 2, 3]);
+    assert_eq!(days(date(1, 20), date(2, 0), false), [1]);
+    assert_eq!(days(date(1, 0), date(1, 0), true), [1]);
+    assert_eq!(days(date(1, 0), date(3, 0), true), [1, 2]);
+    assert_eq!(days(date(1, 10), date(1, 8), false), [1]);
+}
+
 #[test]
 fn create_valarm_example() {
     let mut output = String::new();
@@ -0,0 +1,238 @@
+//! jCal (RFC 7265) rendering of the same events [`generate_ics`](crate::generate_ics::generate_ics) renders.
+
+use chrono::NaiveDateTime;
+use serde_json::{Value, json};
+
+use crate::generate_ics::{
+    SoonToBeIcsEvent, all_day_end, calculate_event_hash, categories, minutes_to_ical_duration,
+    transp, url,
+};
+use crate::location::Buildings;
+use crate::{location, meeting};
+
+pub fn generate_jcal(calendarname: &str, events: &[SoonToBeIcsEvent]) -> String {
+    let properties = json!([
+        ["version", {}, "text", "2.0"],
+        ["method", {}, "text", "PUBLISH"],
+        ["prodid", {}, "text", "https://calendarbot.hawhh.de"],
+        [
+            "x-wr-calname",
+            {},
+            "unknown",
+            format!("@HAWHHCalendarBot ({calendarname})")
+        ],
+    ]);
+
+    let mut components = vec![timezone()];
+    let buildings = location::buildings();
+    components.extend(
+        events
+            .iter()
+            .map(|event| event_as_jcal_vevent(event, buildings)),
+    );
+
+    let calendar = json!(["vcalendar", properties, components]);
+    serde_json::to_string(&calendar).expect("jCal should be serializable")
+}
+
+/// Same timezone as the VTIMEZONE of the ICS output
+fn timezone() -> Value {
+    json!([
+        "vtimezone",
+        [["tzid", {}, "text", "Europe/Berlin"]],
+        [
+            [
+                "daylight",
+                [
+                    ["tzoffsetfrom", {}, "utc-offset", "+01:00"],
+                    ["rrule", {}, "recur", {"freq": "YEARLY", "bymonth": 3, "byday": "-1SU"}],
+                    ["dtstart", {}, "date-time", "1981-03-29T02:00:00"],
+                    ["tzname", {}, "text", "CEST"],
+                    ["tzoffsetto", {}, "utc-offset", "+02:00"],
+                ],
+                [],
+            ],
+            [
+                "standard",
+                [
+                    ["tzoffsetfrom", {}, "utc-offset", "+02:00"],
+                    ["rrule", {}, "recur", {"freq": "YEARLY", "bymonth": 10, "byday": "-1SU"}],
+                    ["dtstart", {}, "date-time", "1996-10-27T03:00:00"],
+                    ["tzname", {}, "text", "CET"],
+                    ["tzoffsetto", {}, "utc-offset", "+01:00"],
+                ],
+                [],
+            ],
+        ],
+    ])
+}
+
+fn event_as_jcal_vevent(event: &SoonToBeIcsEvent, buildings: &Buildings) -> Value {
+    let mut properties = vec![
+        json!(["transp", {}, "text", transp(event)]),
+        json!(["status", {}, "text", event.status.as_ics()]),
+        json!(["summary", {}, "text", event.name]),
+    ];
+    if event.all_day {
+        properties.push(json!([
+            "dtstart",
+            {},
+            "date",
+            event.start_time.format("%Y-%m-%d").to_string()
+        ]));
+        properties.push(json!([
+            "dtend",
+            {},
+            "date",
+            all_day_end(event).format("%Y-%m-%d").to_string()
+        ]));
+    } else {
+        properties.push(json!(["dtstart", {"tzid": "Europe/Berlin"}, "date-time", date_to_jcal_date(event.start_time)]));
+        properties.push(json!(["dtend", {"tzid": "Europe/Berlin"}, "date-time", date_to_jcal_date(event.end_time)]));
+    }
+    let meetings = meeting::meetings_of(event);
+    let meeting_url = meetings.first().map(|meeting| meeting.url.as_str());
+    let (location, geo) = location::for_calendar(&event.location, meeting_url, buildings);
+    if !location.is_empty() {
+        properties.push(json!(["location", {}, "text", location]));
+    }
+    if let Some((latitude, longitude)) = geo {
+        properties.push(json!(["geo", {}, "float", [latitude, longitude]]));
+    }
+    for lecturer in &event.lecturers {
+        properties.push(json!(["contact", {}, "text", lecturer]));
+    }
+    if !event.description.is_empty() {
+        properties.push(json!(["description", {}, "text", event.description]));
+    }
+    let categories = categories(event);
+    if !categories.is_empty() {
+        let mut property = vec![json!("categories"), json!({}), json!("text")];
+        property.extend(categories.into_iter().map(Value::from));
+        properties.push(Value::Array(property));
+    }
+    for meeting in &meetings {
+        properties.push(json!([
+            "conference",
+            {"feature": "VIDEO", "label": meeting.label},
+            "uri",
+            meeting.url
+        ]));
+    }
+    properties.push(json!(["url", {}, "uri", url(event, meeting_url)]));
+    if let Some(original_id) = &event.meta.original_id {
+        properties.push(json!(["x-hawhh-original-id", {}, "text", original_id]));
+    }
+    properties.push(json!([
+        "uid",
+        {},
+        "text",
+        format!("{}@calendarbot.hawhh.de", calculate_event_hash(event))
+    ]));
+
+    let mut components = Vec::new();
+    if let Some(minutes_before) = event.alert_minutes_before {
+        components.push(json!([
+            "valarm",
+            [
+                [
+                    "trigger",
+                    {},
+                    "duration",
+                    format!("-PT{}", minutes_to_ical_duration(minutes_before))
+                ],
+                ["action", {}, "text", "AUDIO"],
+            ],
+            [],
+        ]));
+    }
+
+    json!(["vevent", properties, components])
+}
+
+fn date_to_jcal_date(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+#[cfg(test)]
+fn create_event() -> SoonToBeIcsEvent {
+    SoonToBeIcsEvent {
+        name: "BTI5-VS".to_owned(),
+        status: crate::generate_ics::EventStatus::Cancelled,
+        start_time: chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap(),
+        end_time: chrono::NaiveDate::from_ymd_opt(2020, 8, 22)
+            .unwrap()
+            .and_hms_opt(11, 30, 0)
+            .unwrap(),
+        ..SoonToBeIcsEvent::default()
+    }
+}
+
+#[test]
+fn create_minimal_event_vevent() {
+    let result = event_as_jcal_vevent(&create_event(), &Buildings::new());
+    assert_eq!(
+        result,
+        json!([
+            "vevent",
+            [
+                ["transp", {}, "text", "OPAQUE"],
+                ["status", {}, "text", "CANCELLED"],
+                ["summary", {}, "text", "BTI5-VS"],
+                ["dtstart", {"tzid": "Europe/Berlin"}, "date-time", "2020-08-22T08:30:00"],
+                ["dtend", {"tzid": "Europe/Berlin"}, "date-time", "2020-08-22T11:30:00"],
+                ["url", {}, "uri", "https://telegram.me/HAWHHCalendarBot"],
+                ["uid", {}, "text", "c2238d8b06ddebc8@calendarbot.hawhh.de"],
+            ],
+            [],
+        ])
+    );
+}
+
+#[test]
+fn event_with_details_and_alarm() {
+    let event = SoonToBeIcsEvent {
+        location: "1060".to_owned(),
+        description: "Dozent: HTM\nRaum, neu".to_owned(),
+        alert_minutes_before: Some(90),
+        ..create_event()
+    };
+    let result = event_as_jcal_vevent(&event, &Buildings::new());
+    let properties = result[1].as_array().unwrap();
+    assert!(properties.contains(&json!(["location", {}, "text", "1060"])));
+    assert!(properties.contains(&json!([
+        "description",
+        {},
+        "text",
+        "Dozent: HTM\nRaum, neu"
+    ])));
+    assert_eq!(
+        result[2],
+        json!([[
+            "valarm",
+            [
+                ["trigger", {}, "duration", "-PT01H30M"],
+                ["action", {}, "text", "AUDIO"],
+            ],
+            [],
+        ]])
+    );
+}
+
+#[test]
+fn calendar_structure() {
+    let result = generate_jcal("Peter", &[create_event()]);
+    let parsed: Value = serde_json::from_str(&result).unwrap();
+    assert_eq!(parsed[0], "vcalendar");
+    assert!(parsed[1].as_array().unwrap().contains(&json!([
+        "x-wr-calname",
+        {},
+        "unknown",
+        "@HAWHHCalendarBot (Peter)"
+    ])));
+    assert_eq!(parsed[2][0][0], "vtimezone");
+    assert_eq!(parsed[2][1][0], "vevent");
+}
@@ -0,0 +1,312 @@
+//! Timetable image of one week to be sent in chats. Plain SVG, no external rendering tools required.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Write as _;
+use std::hash::{Hash as _, Hasher as _};
+
+use chrono::{Datelike as _, IsoWeek, NaiveDate, Weekday};
+use quick_xml::escape::escape;
+
+use crate::generate_ics::{EventStatus, days_of};
+use crate::resolved_events::{ResolvedEvent, resolve_events};
+use crate::{timetable, userconfigs};
+
+const WIDTH: f64 = 840.0;
+const TIME_COLUMN: f64 = 48.0;
+const HEADER: f64 = 56.0;
+const HOUR_HEIGHT: f64 = 48.0;
+
+/// Background colors of the events. Every eventfile always gets the same one.
+const COLORS: [&str; 8] = [
+    "#a6cee3", "#b2df8a", "#fb9a99", "#fdbf6f", "#cab2d6", "#ffff99", "#8dd3c7", "#d9d9d9",
+];
+
+const WEEKDAY_NAMES: [&str; 7] = ["Mo", "Di", "Mi", "Do", "Fr", "Sa", "So"];
+
+/// Timetable of the week of the given chat
+pub fn week_svg(chat_id: i64, week: IsoWeek) -> anyhow::Result<String> {
+    let content = userconfigs::load_chat(chat_id)?;
+    let events = resolve_events(content.config);
+    Ok(generate_svg(&content.chat.first_name, week, &events))
+}
+
+pub fn generate_svg(calendarname: &str, week: IsoWeek, events: &[ResolvedEvent]) -> String {
+    // Multi-day events which started in an earlier week still take place in this one
+    let events = events
+        .iter()
+        .filter(|resolved| days_of(&resolved.event).any(|day| day.iso_week() == week))
+        .collect::<Vec<_>>();
+    let monday = NaiveDate::from_isoywd_opt(week.year(), week.week(), Weekday::Mon)
+        .expect("iso week should exist");
+    let starting_this_week = events
+        .iter()
+        .filter(|resolved| resolved.event.start_time.iso_week() == week)
+        .copied()
+        .collect::<Vec<_>>();
+    let days = timetable::shown_days(&starting_this_week);
+    let (events, all_day_events) = timetable::split_all_day(&events);
+    let (first_hour, last_hour) = timetable::hour_range(&events);
+    let day_width = (WIDTH - TIME_COLUMN) / f64::from(days);
+    let height = f64::from(last_hour - first_hour).mul_add(HOUR_HEIGHT, HEADER);
+
+    let mut result = String::new();
+    _ = writeln!(
+        result,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{height}\" viewBox=\"0 0 {WIDTH} {height}\" font-family=\"sans-serif\" font-size=\"11\">"
+    );
+    _ = writeln!(
+        result,
+        "<rect width=\"100%\" height=\"100%\" fill=\"#fff\"/>"
+    );
+    _ = writeln!(
+        result,
+        "<text x=\"4\" y=\"18\" font-size=\"15\" font-weight=\"bold\">{} KW {} ({} - {})</text>",
+        escape(calendarname),
+        week.week(),
+        monday.format("%d.%m."),
+        (monday + chrono::Days::new(6)).format("%d.%m.%Y"),
+    );
+
+    if !all_day_events.is_empty() {
+        let names = all_day_events
+            .iter()
+            .map(|resolved| {
+                format!(
+                    "{} ({})",
+                    resolved.event.name,
+                    timetable::day_range(resolved)
+                )
+            })
+            .collect::<Vec<_>>();
+        _ = writeln!(
+            result,
+            "<text x=\"4\" y=\"32\" font-size=\"10\">Ganztägig: {}</text>",
+            escape(names.join(", "))
+        );
+    }
+
+    for (offset, name) in (0..days).zip(WEEKDAY_NAMES) {
+        let date = monday + chrono::Days::new(offset.into());
+        let x = f64::from(offset).mul_add(day_width, TIME_COLUMN);
+        _ = writeln!(
+            result,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\" font-weight=\"bold\">{name} {}</text>",
+            day_width.mul_add(0.5, x),
+            HEADER - 8.0,
+            date.format("%d.%m."),
+        );
+    }
+    for hour in first_hour..=last_hour {
+        let y = f64::from(hour - first_hour).mul_add(HOUR_HEIGHT, HEADER);
+        _ = writeln!(
+            result,
+            "<line x1=\"0\" y1=\"{y:.1}\" x2=\"{WIDTH}\" y2=\"{y:.1}\" stroke=\"#ddd\"/>"
+        );
+        if hour < last_hour {
+            _ = writeln!(
+                result,
+                "<text x=\"4\" y=\"{:.1}\" fill=\"#666\">{hour:02}:00</text>",
+                y + 12.0
+            );
+        }
+    }
+
+    for offset in 0..days {
+        let date = monday + chrono::Days::new(offset.into());
+        let day_events = events
+            .iter()
+            .filter(|resolved| resolved.event.start_time.date() == date)
+            .copied()
+            .collect::<Vec<_>>();
+        let (lanes, lane_amount) = timetable::lanes(&day_events);
+        let lane_width = day_width / f64::from(lane_amount);
+        let day_x = f64::from(offset).mul_add(day_width, TIME_COLUMN);
+
+        for (resolved, lane) in day_events.iter().zip(lanes) {
+            let (start, end) = timetable::minutes_within(resolved, first_hour, last_hour);
+            event_as_svg(
+                &mut result,
+                resolved,
+                f64::from(lane).mul_add(lane_width, day_x) + 1.0,
+                f64::from(start).mul_add(HOUR_HEIGHT / 60.0, HEADER),
+                lane_width - 2.0,
+                f64::from(end - start) * HOUR_HEIGHT / 60.0,
+            );
+        }
+    }
+
+    result += "</svg>\n";
+    result
+}
+
+fn event_as_svg(
+    output: &mut String,
+    resolved: &ResolvedEvent,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+) {
+    let event = &resolved.event;
+    let cancelled = event.status == EventStatus::Cancelled;
+
+    // Nested svg elements clip their content, long names do not spill into other events
+    _ = write!(
+        output,
+        "<svg x=\"{x:.1}\" y=\"{y:.1}\" width=\"{width:.1}\" height=\"{height:.1}\"{}>",
+        if cancelled { " opacity=\"0.5\"" } else { "" }
+    );
+    _ = write!(
+        output,
+        "<rect width=\"100%\" height=\"100%\" rx=\"3\" fill=\"{}\" stroke=\"#555\"/>",
+        color_of(&resolved.eventfile)
+    );
+    _ = write!(
+        output,
+        "<text x=\"3\" y=\"12\"><tspan>{} - {}</tspan>",
+        event.start_time.format("%H:%M"),
+        event.end_time.format("%H:%M"),
+    );
+    _ = write!(
+        output,
+        "<tspan x=\"3\" dy=\"13\" font-weight=\"bold\"{}>{}</tspan>",
+        if cancelled {
+            " text-decoration=\"line-through\""
+        } else {
+            ""
+        },
+        escape(&event.name),
+    );
+    if !event.location.is_empty() {
+        _ = write!(
+            output,
+            "<tspan x=\"3\" dy=\"13\"{}>{}</tspan>",
+            if resolved.room_changed {
+                " font-weight=\"bold\" fill=\"#c00\""
+            } else {
+                ""
+            },
+            escape(&event.location),
+        );
+    }
+    *output += "</text></svg>\n";
+}
+
+fn color_of(eventfile: &str) -> &'static str {
+    let mut hasher = DefaultHasher::new();
+    eventfile.hash(&mut hasher);
+    let index = usize::try_from(hasher.finish() % COLORS.len() as u64).unwrap_or_default();
+    COLORS[index]
+}
+
+#[cfg(test)]
+fn create_event(day: u32, hour: u32, eventfile: &str) -> ResolvedEvent {
+    let start_time = NaiveDate::from_ymd_opt(2020, 4, day)
+        .unwrap()
+        .and_hms_opt(hour, 15, 0)
+        .unwrap();
+    let mut resolved = crate::resolved_events::test_event(eventfile, start_time, 180);
+    resolved.event.name = format!("{eventfile} Vorlesung");
+    resolved
+}
+
+#[cfg(test)]
+fn week_15() -> IsoWeek {
+    NaiveDate::from_ymd_opt(2020, 4, 6).unwrap().iso_week()
+}
+
+#[test]
+fn svg_is_well_formed_xml() {
+    let mut cancelled = create_event(7, 8, "BTI5-VS");
+    cancelled.event.status = EventStatus::Cancelled;
+    let events = [cancelled, create_event(8, 8, "BTI5-VS <&>")];
+    let svg = generate_svg("Peter & Paul", week_15(), &events);
+    let mut reader = quick_xml::Reader::from_str(&svg);
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Eof) => break,
+            Ok(_) => {}
+            Err(err) => panic!("invalid xml: {err}\n{svg}"),
+        }
+    }
+}
+
+#[test]
+fn only_events_of_the_week_are_shown() {
+    let events = [
+        create_event(2, 8, "BTI5-VS"),
+        create_event(7, 8, "BTI5-VS"),
+        create_event(14, 8, "BTI5-VS"),
+    ];
+    let svg = generate_svg("Peter", week_15(), &events);
+    assert!(svg.contains("KW 15 (06.04. - 12.04.2020)"), "{svg}");
+    assert_eq!(
+        svg.matches("<rect width=\"100%\" height=\"100%\" rx")
+            .count(),
+        1
+    );
+    assert!(svg.contains("<text x=\"4\" y=\"68.0\" fill=\"#666\">08:00</text>"));
+}
+
+#[test]
+fn all_day_events_are_listed_in_the_header() {
+    let mut all_day = create_event(6, 0, "Projektwoche");
+    all_day.event.start_time = all_day.event.start_time.date().into();
+    all_day.event.end_time = all_day.event.start_time + chrono::Days::new(5);
+    all_day.event.all_day = true;
+    let svg = generate_svg(
+        "Peter",
+        week_15(),
+        &[all_day, create_event(7, 8, "BTI5-VS")],
+    );
+    assert!(
+        svg.contains("Ganztägig: Projektwoche Vorlesung (06.04. - 10.04.)</text>"),
+        "{svg}"
+    );
+    assert_eq!(
+        svg.matches("<rect width=\"100%\" height=\"100%\" rx")
+            .count(),
+        1
+    );
+    assert!(svg.contains("<text x=\"4\" y=\"68.0\" fill=\"#666\">08:00</text>"));
+}
+
+#[test]
+fn multi_day_events_of_the_previous_week_are_shown() {
+    let mut excursion = create_event(4, 0, "Exkursion");
+    excursion.event.start_time = excursion.event.start_time.date().into();
+    excursion.event.end_time = excursion.event.start_time + chrono::Days::new(4);
+    excursion.event.all_day = true;
+    let svg = generate_svg("Peter", week_15(), &[excursion]);
+    assert!(
+        svg.contains("Ganztägig: Exkursion Vorlesung (04.04. - 07.04.)</text>"),
+        "{svg}"
+    );
+    assert!(!svg.contains(">Sa 11.04.<"), "{svg}");
+}
+
+#[test]
+fn same_eventfile_same_color() {
+    assert_eq!(color_of("BTI5-VS"), color_of("BTI5-VS"));
+    let svg = generate_svg(
+        "Peter",
+        week_15(),
+        &[create_event(7, 8, "BTI5-VS"), create_event(9, 8, "BTI5-VS")],
+    );
+    assert_eq!(svg.matches(color_of("BTI5-VS")).count(), 2, "{svg}");
+}
+
+#[test]
+fn room_changes_and_cancellations_are_marked() {
+    let mut moved = create_event(7, 8, "BTI5-VS");
+    moved.room_changed = true;
+    let mut cancelled = create_event(8, 8, "BTI5-VS");
+    cancelled.event.status = EventStatus::Cancelled;
+    let svg = generate_svg("Peter", week_15(), &[moved, cancelled]);
+    assert!(
+        svg.contains("font-weight=\"bold\" fill=\"#c00\">1060"),
+        "{svg}"
+    );
+    assert!(svg.contains("opacity=\"0.5\""), "{svg}");
+    assert!(svg.contains("text-decoration=\"line-through\""), "{svg}");
+}
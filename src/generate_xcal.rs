@@ -0,0 +1,463 @@
+//! xCal (RFC 6321) rendering of the same events [`generate_ics`](crate::generate_ics::generate_ics) renders.
+
+use std::fmt::Write as _;
+
+use chrono::NaiveDateTime;
+use quick_xml::escape::escape;
+
+use crate::generate_ics::{
+    SoonToBeIcsEvent, all_day_end, calculate_event_hash, categories, minutes_to_ical_duration,
+    transp, url,
+};
+use crate::location::Buildings;
+use crate::{location, meeting};
+
+const XCAL_TIMEZONE: &str = "<vtimezone><properties><tzid><text>Europe/Berlin</text></tzid></properties><components>
+<daylight><properties><tzoffsetfrom><utc-offset>+01:00</utc-offset></tzoffsetfrom><rrule><recur><freq>YEARLY</freq><bymonth>3</bymonth><byday>-1SU</byday></recur></rrule><dtstart><date-time>1981-03-29T02:00:00</date-time></dtstart><tzname><text>CEST</text></tzname><tzoffsetto><utc-offset>+02:00</utc-offset></tzoffsetto></properties></daylight>
+<standard><properties><tzoffsetfrom><utc-offset>+02:00</utc-offset></tzoffsetfrom><rrule><recur><freq>YEARLY</freq><bymonth>10</bymonth><byday>-1SU</byday></recur></rrule><dtstart><date-time>1996-10-27T03:00:00</date-time></dtstart><tzname><text>CET</text></tzname><tzoffsetto><utc-offset>+01:00</utc-offset></tzoffsetto></properties></standard>
+</components></vtimezone>
+";
+
+pub fn generate_xcal(calendarname: &str, events: &[SoonToBeIcsEvent]) -> String {
+    generate_xcal_with_buildings(calendarname, events, location::buildings())
+}
+
+/// [`generate_xcal`] with the given buildings for the addresses and coordinates of the locations
+pub fn generate_xcal_with_buildings(
+    calendarname: &str,
+    events: &[SoonToBeIcsEvent],
+    buildings: &Buildings,
+) -> String {
+    let mut result = String::new();
+    result += "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n";
+    result += "<icalendar xmlns=\"urn:ietf:params:xml:ns:icalendar-2.0\"><vcalendar>\n";
+
+    result += "<properties>\n";
+    property(&mut result, "version", &[], "text", "2.0");
+    property(&mut result, "method", &[], "text", "PUBLISH");
+    property(
+        &mut result,
+        "prodid",
+        &[],
+        "text",
+        "https://calendarbot.hawhh.de",
+    );
+    property(
+        &mut result,
+        "x-wr-calname",
+        &[],
+        "unknown",
+        &format!("@HAWHHCalendarBot ({calendarname})"),
+    );
+    result += "</properties>\n";
+
+    result += "<components>\n";
+    result += XCAL_TIMEZONE;
+    for event in events {
+        event_as_xcal_vevent(&mut result, event, buildings);
+    }
+    result += "</components>\n";
+
+    result += "</vcalendar></icalendar>\n";
+    result
+}
+
+fn event_as_xcal_vevent(output: &mut String, event: &SoonToBeIcsEvent, buildings: &Buildings) {
+    *output += "<vevent><properties>\n";
+    property(output, "transp", &[], "text", transp(event));
+    property(output, "status", &[], "text", event.status.as_ics());
+    property(output, "summary", &[], "text", &event.name);
+    dates(output, event);
+    let meetings = meeting::meetings_of(event);
+    let meeting_url = meetings.first().map(|meeting| meeting.url.as_str());
+    let (location, geo) = location::for_calendar(&event.location, meeting_url, buildings);
+    if !location.is_empty() {
+        property(output, "location", &[], "text", &location);
+    }
+    if let Some((latitude, longitude)) = geo {
+        _ = writeln!(
+            output,
+            "<geo><latitude>{latitude}</latitude><longitude>{longitude}</longitude></geo>"
+        );
+    }
+    for lecturer in &event.lecturers {
+        property(output, "contact", &[], "text", lecturer);
+    }
+    if !event.description.is_empty() {
+        property(output, "description", &[], "text", &event.description);
+    }
+    let categories = categories(event);
+    if !categories.is_empty() {
+        *output += "<categories>";
+        for category in categories {
+            _ = write!(output, "<text>{}</text>", escape(category));
+        }
+        *output += "</categories>\n";
+    }
+    for meeting in &meetings {
+        property(
+            output,
+            "conference",
+            &[("feature", "VIDEO"), ("label", meeting.label)],
+            "uri",
+            &meeting.url,
+        );
+    }
+    property(output, "url", &[], "uri", url(event, meeting_url));
+    if let Some(original_id) = &event.meta.original_id {
+        property(output, "x-hawhh-original-id", &[], "text", original_id);
+    }
+    property(
+        output,
+        "uid",
+        &[],
+        "text",
+        &format!("{}@calendarbot.hawhh.de", calculate_event_hash(event)),
+    );
+    *output += "</properties>";
+
+    if let Some(minutes_before) = event.alert_minutes_before {
+        *output += "<components><valarm><properties>\n";
+        property(
+            output,
+            "trigger",
+            &[],
+            "duration",
+            &format!("-PT{}", minutes_to_ical_duration(minutes_before)),
+        );
+        property(output, "action", &[], "text", "AUDIO");
+        *output += "</properties></valarm></components>";
+    }
+
+    *output += "</vevent>\n";
+}
+
+fn dates(output: &mut String, event: &SoonToBeIcsEvent) {
+    if event.all_day {
+        property(
+            output,
+            "dtstart",
+            &[],
+            "date",
+            &event.start_time.format("%Y-%m-%d").to_string(),
+        );
+        property(
+            output,
+            "dtend",
+            &[],
+            "date",
+            &all_day_end(event).format("%Y-%m-%d").to_string(),
+        );
+    } else {
+        property(
+            output,
+            "dtstart",
+            &[("tzid", "Europe/Berlin")],
+            "date-time",
+            &date_to_xcal_date(event.start_time),
+        );
+        property(
+            output,
+            "dtend",
+            &[("tzid", "Europe/Berlin")],
+            "date-time",
+            &date_to_xcal_date(event.end_time),
+        );
+    }
+}
+
+fn property(
+    output: &mut String,
+    name: &str,
+    parameters: &[(&str, &str)],
+    value_type: &str,
+    value: &str,
+) {
+    _ = write!(output, "<{name}>");
+    if !parameters.is_empty() {
+        *output += "<parameters>";
+        for (parameter, value) in parameters {
+            _ = write!(
+                output,
+                "<{parameter}><text>{}</text></{parameter}>",
+                escape(*value)
+            );
+        }
+        *output += "</parameters>";
+    }
+    _ = writeln!(
+        output,
+        "<{value_type}>{}</{value_type}></{name}>",
+        escape(value)
+    );
+}
+
+fn date_to_xcal_date(date: NaiveDateTime) -> String {
+    date.format("%Y-%m-%dT%H:%M:%S").to_string()
+}
+
+/// Format independent representation of a calendar component to compare the ICS and xCal output
+#[cfg(test)]
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Component {
+    name: String,
+    /// `name;param=value:value` with normalized values
+    properties: Vec<String>,
+    components: Vec<Self>,
+}
+
+#[cfg(test)]
+fn ics_to_component(ics: &str) -> Component {
+    let mut stack = vec![Component::default()];
+    for line in ics.lines() {
+        if let Some(name) = line.strip_prefix("BEGIN:") {
+            stack.push(Component {
+                name: name.to_lowercase(),
+                ..Component::default()
+            });
+        } else if line.starts_with("END:") {
+            let component = stack.pop().unwrap();
+            stack.last_mut().unwrap().components.push(component);
+        } else {
+            let (name_and_params, value) = line.split_once(':').unwrap();
+            let mut parts = name_and_params.split(';');
+            let mut normalized = parts.next().unwrap().to_lowercase();
+            for param in parts.filter(|param| !param.starts_with("VALUE=")) {
+                let (key, value) = param.split_once('=').unwrap();
+                _ = write!(normalized, ";{}={value}", key.to_lowercase());
+            }
+            let value = value
+                .replace("\\n", "\n")
+                .replace("\\,", ",")
+                .replace("\\;", ";")
+                .replace("\\\\", "\\");
+            _ = write!(normalized, ":{value}");
+            stack.last_mut().unwrap().properties.push(normalized);
+        }
+    }
+    stack.pop().unwrap().components.pop().unwrap()
+}
+
+#[cfg(test)]
+fn xcal_to_component(xcal: &str) -> Component {
+    use quick_xml::Reader;
+    use quick_xml::events::Event;
+
+    /// xCal strictly nests components, properties, parameters and values
+    enum Level {
+        Components,
+        Component,
+        Properties,
+        Property(String),
+        Parameters,
+        Parameter(String),
+        ParameterValue,
+        Value(String),
+        RecurPart(String),
+    }
+
+    let mut reader = Reader::from_str(xcal);
+    let mut components = vec![Component::default()];
+    let mut levels: Vec<Level> = Vec::new();
+    let mut params = String::new();
+    let mut value = String::new();
+
+    loop {
+        match reader.read_event().unwrap() {
+            Event::Start(element) => {
+                let name = String::from_utf8(element.local_name().as_ref().to_vec()).unwrap();
+                let level = match (levels.last(), name.as_str()) {
+                    (None, _) | (Some(Level::Component), "components") => Level::Components,
+                    (Some(Level::Components), _) => {
+                        components.push(Component {
+                            name,
+                            ..Component::default()
+                        });
+                        Level::Component
+                    }
+                    (Some(Level::Component), _) => Level::Properties,
+                    (Some(Level::Properties), _) => {
+                        params.clear();
+                        value.clear();
+                        Level::Property(name)
+                    }
+                    (Some(Level::Property(_)), "parameters") => Level::Parameters,
+                    (Some(Level::Parameters), _) => Level::Parameter(name),
+                    (Some(Level::Parameter(_)), _) => Level::ParameterValue,
+                    (Some(Level::Value(_)), _) => Level::RecurPart(name),
+                    (Some(Level::Property(_)), _) => {
+                        // Multiple values like of categories or geo like the ICS form `latitude;longitude`
+                        if !value.is_empty() {
+                            value.push(if name == "longitude" { ';' } else { ',' });
+                        }
+                        Level::Value(name)
+                    }
+                    _ => Level::Value(name),
+                };
+                levels.push(level);
+            }
+            Event::Text(text) => {
+                let text = text.decode().unwrap();
+                match levels.last() {
+                    Some(Level::Value(value_type)) if value_type == "utc-offset" => {
+                        value += &text.replace(':', "");
+                    }
+                    Some(Level::Value(value_type))
+                        if value_type == "date-time" || value_type == "date" =>
+                    {
+                        value += &text.replace(['-', ':'], "");
+                    }
+                    Some(Level::RecurPart(part)) => {
+                        if !value.is_empty() {
+                            value.push(';');
+                        }
+                        _ = write!(value, "{}={text}", part.to_uppercase());
+                    }
+                    Some(Level::ParameterValue) => {
+                        let Level::Parameter(param) = &levels[levels.len() - 2] else {
+                            unreachable!("parameter values are always within a parameter");
+                        };
+                        _ = write!(params, ";{param}={text}");
+                    }
+                    Some(Level::Value(_)) => value += &text,
+                    // whitespace between elements
+                    _ => {}
+                }
+            }
+            Event::GeneralRef(reference) => {
+                value += entity(&reference.decode().unwrap());
+            }
+            Event::End(_) => match levels.pop().unwrap() {
+                Level::Property(name) => {
+                    components
+                        .last_mut()
+                        .unwrap()
+                        .properties
+                        .push(format!("{name}{params}:{value}"));
+                }
+                Level::Component => {
+                    let component = components.pop().unwrap();
+                    components.last_mut().unwrap().components.push(component);
+                }
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    components.pop().unwrap().components.pop().unwrap()
+}
+
+#[cfg(test)]
+fn entity(name: &str) -> &'static str {
+    match name {
+        "amp" => "&",
+        "lt" => "<",
+        "gt" => ">",
+        "quot" => "\"",
+        "apos" => "'",
+        other => panic!("unexpected entity {other}"),
+    }
+}
+
+#[cfg(test)]
+fn create_events() -> Vec<SoonToBeIcsEvent> {
+    let date = |day: u32, hour: u32| {
+        chrono::NaiveDate::from_ymd_opt(2020, 8, day)
+            .unwrap()
+            .and_hms_opt(hour, 30, 0)
+            .unwrap()
+    };
+    vec![
+        SoonToBeIcsEvent {
+            name: "BTI5-VS".to_owned(),
+            status: crate::generate_ics::EventStatus::Cancelled,
+            start_time: date(22, 8),
+            end_time: date(22, 11),
+            ..SoonToBeIcsEvent::default()
+        },
+        SoonToBeIcsEvent {
+            name: "BTI5-VSP/01 <Praktikum> & more".to_owned(),
+            start_time: date(29, 14),
+            end_time: date(29, 17),
+            alert_minutes_before: Some(90),
+            description: "Dozent: HTM\n\nnotes; with, special \\ chars\nhttps://haw-hamburg.zoom.us/j/1?pwd=a&b".to_owned(),
+            location: "BT7 R1.060, 1061".to_owned(),
+            lecturers: vec!["HTM".to_owned()],
+            meta: crate::generate_ics::EventMeta {
+                event_type: Some(crate::generate_ics::EventType::Lab),
+                url: None,
+                categories: vec!["Pflicht, Wahl".to_owned()],
+                group: Some("BTI5-VSP/01".to_owned()),
+                original_id: Some("42".to_owned()),
+            },
+            ..SoonToBeIcsEvent::default()
+        },
+        SoonToBeIcsEvent {
+            name: "Prüfungszeitraum".to_owned(),
+            status: crate::generate_ics::EventStatus::Tentative,
+            start_time: date(31, 0),
+            end_time: date(31, 0) + chrono::Days::new(4),
+            all_day: true,
+            transparent: true,
+            meta: crate::generate_ics::EventMeta {
+                url: Some("https://www.haw-hamburg.de/pruefungen".to_owned()),
+                ..crate::generate_ics::EventMeta::default()
+            },
+            ..SoonToBeIcsEvent::default()
+        },
+    ]
+}
+
+#[test]
+fn minimal_event_vevent() {
+    let mut result = String::new();
+    event_as_xcal_vevent(&mut result, &create_events()[0], &Buildings::new());
+    assert_eq!(
+        result,
+        "<vevent><properties>
+<transp><text>OPAQUE</text></transp>
+<status><text>CANCELLED</text></status>
+<summary><text>BTI5-VS</text></summary>
+<dtstart><parameters><tzid><text>Europe/Berlin</text></tzid></parameters><date-time>2020-08-22T08:30:00</date-time></dtstart>
+<dtend><parameters><tzid><text>Europe/Berlin</text></tzid></parameters><date-time>2020-08-22T11:30:00</date-time></dtend>
+<url><uri>https://telegram.me/HAWHHCalendarBot</uri></url>
+<uid><text>c2238d8b06ddebc8@calendarbot.hawhh.de</text></uid>
+</properties></vevent>
+"
+    );
+}
+
+#[test]
+fn xcal_is_well_formed_xml() {
+    let xcal = generate_xcal("Peter & Paul", &create_events());
+    let mut reader = quick_xml::Reader::from_str(&xcal);
+    loop {
+        match reader.read_event() {
+            Ok(quick_xml::events::Event::Eof) => break,
+            Ok(_) => {}
+            Err(err) => panic!("invalid xml: {err}\n{xcal}"),
+        }
+    }
+}
+
+#[test]
+fn xcal_is_equivalent_to_ics() {
+    let events = create_events();
+    let buildings = location::test_buildings();
+    let ics = crate::generate_ics::generate_ics_with_buildings("Peter & Paul", &events, &buildings);
+    let xcal = generate_xcal_with_buildings("Peter & Paul", &events, &buildings);
+
+    let from_ics = ics_to_component(&ics);
+    let from_xcal = xcal_to_component(&xcal);
+
+    assert_eq!(from_ics.name, "vcalendar");
+    assert_eq!(from_xcal.components.len(), 4);
+    assert!(ics.contains("GEO:53.5565;10.0225"), "{ics}");
+    assert!(
+        xcal.contains("<geo><latitude>53.5565</latitude><longitude>10.0225</longitude></geo>"),
+        "{xcal}"
+    );
+    assert_eq!(from_xcal, from_ics);
+}
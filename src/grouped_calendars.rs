@@ -0,0 +1,112 @@
+//! Calendars of events grouped across all eventfiles like by room or lecturer: `<key>.ics` and an index of the groups.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context as _;
+
+use crate::changestatus::{Changestatus, Changetype};
+use crate::generate_ics::{SoonToBeIcsEvent, generate_ics};
+use crate::output_files::write_atomic;
+use crate::resolved_events::ResolvedEvent;
+
+pub const INDEX: &str = "index.json";
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IndexEntry {
+    /// Name of the group like the room `BT7 1.060`
+    pub name: String,
+    /// Filename of the calendar within the folder
+    pub calendar: String,
+    pub event_count: usize,
+}
+
+/// Events of one group
+pub struct Group {
+    pub name: String,
+    pub events: Vec<SoonToBeIcsEvent>,
+}
+
+/// Events of every group by the key of the group.
+/// An event belongs to every group of the `(key, name)` pairs returned for it.
+pub fn group_events<F>(events: &[ResolvedEvent], groups_of: F) -> BTreeMap<String, Group>
+where
+    F: Fn(&SoonToBeIcsEvent) -> Vec<(String, String)>,
+{
+    let mut groups: BTreeMap<String, Group> = BTreeMap::new();
+    for resolved in events {
+        for (key, name) in groups_of(&resolved.event) {
+            let group = groups.entry(key).or_insert_with(|| Group {
+                name,
+                events: Vec::new(),
+            });
+            // The same module is often listed in multiple eventfiles
+            if !group.events.contains(&resolved.event) {
+                group.events.push(resolved.event.clone());
+            }
+        }
+    }
+    for group in groups.values_mut() {
+        group.events.sort_by_cached_key(|event| event.start_time);
+    }
+    groups
+}
+
+/// Write the calendars of the groups which changed, the index and remove calendars of groups which no longer exist
+pub fn write_all_in(
+    target: &Path,
+    groups: &BTreeMap<String, Group>,
+) -> anyhow::Result<Vec<Changestatus>> {
+    fs::create_dir_all(target).context("failed to create folder")?;
+
+    let mut changestati = Vec::new();
+    let mut index = Vec::new();
+    for (key, group) in groups {
+        let calendar = format!("{key}.ics");
+        let path = target.join(&calendar);
+        let content = generate_ics(&group.name, &group.events);
+        let changetype = match fs::read_to_string(&path) {
+            Ok(existing) if existing == content => Changetype::Same,
+            Ok(_) => Changetype::Changed,
+            Err(_) => Changetype::Added,
+        };
+        if changetype != Changetype::Same {
+            write_atomic(&path, content.as_bytes())
+                .with_context(|| format!("failed to write calendar {calendar}"))?;
+        }
+        changestati.push(Changestatus {
+            name: group.name.clone(),
+            changetype,
+        });
+        index.push(IndexEntry {
+            name: group.name.clone(),
+            calendar,
+            event_count: group.events.len(),
+        });
+    }
+
+    write_atomic(
+        &target.join(INDEX),
+        serde_json::to_string_pretty(&index)?.as_bytes(),
+    )
+    .context("failed to write index")?;
+
+    for maybe_entry in fs::read_dir(target).context("failed to read folder")? {
+        let filename = maybe_entry?.file_name().to_string_lossy().into_owned();
+        let Some(key) = filename.strip_suffix(".ics") else {
+            continue;
+        };
+        if !filename.starts_with('.') && !groups.contains_key(key) {
+            fs::remove_file(target.join(&filename))
+                .with_context(|| format!("failed to remove calendar {filename}"))?;
+            changestati.push(Changestatus {
+                name: filename,
+                changetype: Changetype::Removed,
+            });
+        }
+    }
+
+    Ok(changestati)
+}
@@ -0,0 +1,538 @@
+use std::fs;
+use std::io::Read as _;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::artifacts::{self, Metadata};
+use crate::caldav::{self, SyncHistory};
+
+pub const NOT_FOUND: &str = "Not Found";
+const MAX_BODY_BYTES: u64 = 1024 * 1024;
+
+/// Relevant parts of an incoming request independent of the HTTP library
+#[derive(Debug, Default)]
+pub struct IncomingRequest {
+    pub method: String,
+    pub url: String,
+    pub accept_encoding: Option<String>,
+    pub if_none_match: Option<String>,
+    pub if_modified_since: Option<String>,
+    pub depth: Option<String>,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Reply {
+    pub fn plain(status: u16, text: &str) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type", "text/plain; charset=utf-8".to_owned())],
+            body: text.as_bytes().to_vec(),
+        }
+    }
+
+    #[cfg(test)]
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    const fn etag_suffix(self) -> &'static str {
+        match self {
+            Self::Identity => "",
+            Self::Gzip => "-gzip",
+            Self::Brotli => "-br",
+        }
+    }
+}
+
+/// Serve the calendars folder on the given address (like `0.0.0.0:8080`) in a background thread.
+pub fn spawn(address: &str, folder: PathBuf) -> anyhow::Result<()> {
+    let server = Server::http(address)
+        .map_err(|err| anyhow::anyhow!("{err}"))
+        .with_context(|| format!("failed to listen on {address}"))?;
+    std::thread::Builder::new()
+        .name("http".to_owned())
+        .spawn(move || serve(&server, &folder, &SyncHistory::default()))
+        .context("failed to spawn http server thread")?;
+    Ok(())
+}
+
+fn serve(server: &Server, folder: &Path, history: &SyncHistory) {
+    for mut request in server.incoming_requests() {
+        let reply = respond(folder, history, &incoming_request(&mut request));
+        let mut response = Response::from_data(reply.body).with_status_code(reply.status);
+        for (key, value) in reply.headers {
+            if let Ok(header) = Header::from_bytes(key.as_bytes(), value.as_bytes()) {
+                response.add_header(header);
+            }
+        }
+        if let Err(err) = request.respond(response) {
+            println!("http failed to respond: {err}");
+        }
+    }
+}
+
+fn incoming_request(request: &mut Request) -> IncomingRequest {
+    let mut body = String::new();
+    if let Err(err) = request
+        .as_reader()
+        .take(MAX_BODY_BYTES)
+        .read_to_string(&mut body)
+    {
+        println!("http failed to read request body: {err}");
+    }
+    IncomingRequest {
+        method: request.method().as_str().to_owned(),
+        url: request.url().to_owned(),
+        accept_encoding: request_header(request, "Accept-Encoding"),
+        if_none_match: request_header(request, "If-None-Match"),
+        if_modified_since: request_header(request, "If-Modified-Since"),
+        depth: request_header(request, "Depth"),
+        body,
+    }
+}
+
+fn request_header(request: &Request, name: &'static str) -> Option<String> {
+    request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str().to_owned())
+}
+
+pub fn respond(folder: &Path, history: &SyncHistory, request: &IncomingRequest) -> Reply {
+    if request.url.starts_with(caldav::PREFIX) {
+        return caldav::respond(folder, history, request);
+    }
+
+    if !matches!(request.method.as_str(), "GET" | "HEAD") {
+        let mut reply = Reply::plain(405, "Method Not Allowed");
+        reply.headers.push(("Allow", "GET, HEAD".to_owned()));
+        return reply;
+    }
+
+    let path = request.url.split(['?', '#']).next().unwrap_or_default();
+    let Some(filename) = path
+        .strip_prefix('/')
+        .filter(|name| is_calendar_filename(name))
+    else {
+        return Reply::plain(404, NOT_FOUND);
+    };
+    let file_path = folder.join(filename);
+    let Ok(metadata) = fs::metadata(&file_path) else {
+        return Reply::plain(404, NOT_FOUND);
+    };
+    let stem = artifacts::stem(filename).unwrap_or(filename);
+    let path_of = |extension: &str| folder.join(format!("{stem}{extension}"));
+
+    let encoding = negotiate_encoding(
+        request.accept_encoding.as_deref(),
+        path_of(artifacts::BROTLI).exists(),
+    );
+    // The sidecar saves hashing the calendar on every request
+    let sha256 = match Metadata::read(&path_of(artifacts::METADATA)) {
+        Some(sidecar) => sidecar.sha256,
+        None => match fs::read(&file_path) {
+            Ok(content) => artifacts::sha256_hex(&content),
+            Err(_) => return Reply::plain(404, NOT_FOUND),
+        },
+    };
+    let etag = format!("\"{sha256}{}\"", encoding.etag_suffix());
+    let last_modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let mut headers = vec![
+        ("ETag", etag.clone()),
+        ("Last-Modified", httpdate::fmt_http_date(last_modified)),
+        ("Vary", "Accept-Encoding".to_owned()),
+        ("Cache-Control", "no-cache".to_owned()),
+    ];
+
+    if is_not_modified(request, &etag, last_modified) {
+        return Reply {
+            status: 304,
+            headers,
+            body: Vec::new(),
+        };
+    }
+
+    headers.push(("Content-Type", "text/calendar; charset=utf-8".to_owned()));
+    let body = match encoding {
+        Encoding::Identity => fs::read(&file_path),
+        Encoding::Gzip => {
+            headers.push(("Content-Encoding", "gzip".to_owned()));
+            // Calendars without the precompressed variant are compressed on demand
+            fs::read(path_of(artifacts::GZIP))
+                .or_else(|_| fs::read(&file_path).map(|content| artifacts::gzip(&content)))
+        }
+        Encoding::Brotli => {
+            headers.push(("Content-Encoding", "br".to_owned()));
+            fs::read(path_of(artifacts::BROTLI))
+        }
+    };
+    let Ok(body) = body else {
+        return Reply::plain(404, NOT_FOUND);
+    };
+
+    Reply {
+        status: 200,
+        headers,
+        body,
+    }
+}
+
+/// Only `<chat id>-<suffix>.ics` is served. Everything else is indistinguishable from a missing calendar.
+pub fn is_calendar_filename(filename: &str) -> bool {
+    let Some(stem) = filename.strip_suffix(".ics") else {
+        return false;
+    };
+    let Some((chat_id, suffix)) = stem.trim_start_matches('-').split_once('-') else {
+        return false;
+    };
+    !chat_id.is_empty()
+        && chat_id.chars().all(|char| char.is_ascii_digit())
+        && !suffix.is_empty()
+        && suffix
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '-' || char == '_')
+}
+
+/// `If-None-Match` takes precedence over `If-Modified-Since`
+fn is_not_modified(request: &IncomingRequest, etag: &str, last_modified: SystemTime) -> bool {
+    if let Some(if_none_match) = &request.if_none_match {
+        return if_none_match
+            .split(',')
+            .map(str::trim)
+            .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag);
+    }
+
+    request
+        .if_modified_since
+        .as_deref()
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| {
+            // HTTP dates have second precision
+            let last_modified = httpdate::parse_http_date(&httpdate::fmt_http_date(last_modified))
+                .unwrap_or(last_modified);
+            last_modified <= since
+        })
+}
+
+/// Brotli is only offered when the precompressed variant exists, compressing it on demand is too slow
+fn negotiate_encoding(accept_encoding: Option<&str>, brotli_available: bool) -> Encoding {
+    let Some(accept_encoding) = accept_encoding else {
+        return Encoding::Identity;
+    };
+    let mut best = (Encoding::Identity, 0.0);
+    for part in accept_encoding.split(',') {
+        let mut params = part.split(';').map(str::trim);
+        let encoding = match params
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "br" if brotli_available => Encoding::Brotli,
+            "gzip" | "x-gzip" => Encoding::Gzip,
+            _ => continue,
+        };
+        let quality = params
+            .find_map(|param| param.strip_prefix("q="))
+            .and_then(|quality| quality.parse::<f32>().ok())
+            .unwrap_or(1.0);
+        // Prefer brotli on equal quality as it compresses better
+        if quality > best.1 || (quality > 0.0 && quality >= best.1 && encoding == Encoding::Brotli)
+        {
+            best = (encoding, quality);
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+const TEST_CALENDAR: &str = "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nEND:VCALENDAR\r\n";
+
+#[cfg(test)]
+fn create_folder() -> tempfile::TempDir {
+    let tempdir = tempfile::tempdir().unwrap();
+    fs::write(tempdir.path().join("1337-secret.ics"), TEST_CALENDAR).unwrap();
+    fs::write(tempdir.path().join("notes.txt"), "private").unwrap();
+    tempdir
+}
+
+#[cfg(test)]
+fn get(url: &str) -> IncomingRequest {
+    IncomingRequest {
+        method: "GET".to_owned(),
+        url: url.to_owned(),
+        ..IncomingRequest::default()
+    }
+}
+
+#[test]
+fn calendar_filename_examples() {
+    assert!(is_calendar_filename("1337-secret.ics"));
+    assert!(is_calendar_filename("-1001337-se_cr-et.ics"));
+    assert!(!is_calendar_filename("1337-secret.json"));
+    assert!(!is_calendar_filename("1337.ics"));
+    assert!(!is_calendar_filename("abc-secret.ics"));
+    assert!(!is_calendar_filename("../1337-secret.ics"));
+    assert!(!is_calendar_filename("1337-sec/ret.ics"));
+    assert!(!is_calendar_filename(".1337-secret.ics.tmp"));
+}
+
+#[test]
+fn serves_calendar() {
+    let folder = create_folder();
+    let reply = respond(
+        folder.path(),
+        &SyncHistory::default(),
+        &get("/1337-secret.ics?cache=1"),
+    );
+    assert_eq!(reply.status, 200);
+    assert_eq!(
+        reply.header("content-type"),
+        Some("text/calendar; charset=utf-8")
+    );
+    assert_eq!(reply.header("Content-Encoding"), None);
+    assert!(reply.header("Last-Modified").is_some());
+    assert_eq!(reply.body, TEST_CALENDAR.as_bytes());
+}
+
+#[test]
+fn unknown_names_are_plain_not_found() {
+    let folder = create_folder();
+    for url in [
+        "/1338-secret.ics",
+        "/1337-other.ics",
+        "/notes.txt",
+        "/../1337-secret.ics",
+        "/",
+    ] {
+        let reply = respond(folder.path(), &SyncHistory::default(), &get(url));
+        assert_eq!(reply.status, 404, "{url}");
+        assert_eq!(reply.body, NOT_FOUND.as_bytes(), "{url}");
+    }
+}
+
+#[test]
+fn other_methods_are_not_allowed() {
+    let folder = create_folder();
+    let request = IncomingRequest {
+        method: "POST".to_owned(),
+        ..get("/1337-secret.ics")
+    };
+    assert_eq!(
+        respond(folder.path(), &SyncHistory::default(), &request).status,
+        405
+    );
+}
+
+#[test]
+fn matching_etag_is_not_modified() {
+    let folder = create_folder();
+    let first = respond(
+        folder.path(),
+        &SyncHistory::default(),
+        &get("/1337-secret.ics"),
+    );
+    let etag = first.header("ETag").unwrap().to_owned();
+
+    let request = IncomingRequest {
+        if_none_match: Some(format!("\"other\", {etag}")),
+        ..get("/1337-secret.ics")
+    };
+    let reply = respond(folder.path(), &SyncHistory::default(), &request);
+    assert_eq!(reply.status, 304);
+    assert!(reply.body.is_empty());
+
+    let request = IncomingRequest {
+        if_none_match: Some("\"other\"".to_owned()),
+        ..get("/1337-secret.ics")
+    };
+    assert_eq!(
+        respond(folder.path(), &SyncHistory::default(), &request).status,
+        200
+    );
+}
+
+#[test]
+fn last_modified_is_not_modified() {
+    let folder = create_folder();
+    let first = respond(
+        folder.path(),
+        &SyncHistory::default(),
+        &get("/1337-secret.ics"),
+    );
+    let last_modified = first.header("Last-Modified").unwrap().to_owned();
+
+    let request = IncomingRequest {
+        if_modified_since: Some(last_modified),
+        ..get("/1337-secret.ics")
+    };
+    assert_eq!(
+        respond(folder.path(), &SyncHistory::default(), &request).status,
+        304
+    );
+
+    let request = IncomingRequest {
+        if_modified_since: Some("Thu, 01 Jan 1970 00:00:00 GMT".to_owned()),
+        ..get("/1337-secret.ics")
+    };
+    assert_eq!(
+        respond(folder.path(), &SyncHistory::default(), &request).status,
+        200
+    );
+}
+
+#[test]
+fn negotiate_encoding_examples() {
+    assert_eq!(negotiate_encoding(None, true), Encoding::Identity);
+    assert_eq!(
+        negotiate_encoding(Some("identity"), true),
+        Encoding::Identity
+    );
+    assert_eq!(negotiate_encoding(Some("gzip"), true), Encoding::Gzip);
+    assert_eq!(
+        negotiate_encoding(Some("gzip, deflate, br"), true),
+        Encoding::Brotli
+    );
+    assert_eq!(
+        negotiate_encoding(Some("gzip, deflate, br"), false),
+        Encoding::Gzip
+    );
+    assert_eq!(negotiate_encoding(Some("br"), false), Encoding::Identity);
+    assert_eq!(
+        negotiate_encoding(Some("br;q=0.5, gzip"), true),
+        Encoding::Gzip
+    );
+    assert_eq!(
+        negotiate_encoding(Some("br;q=0, gzip;q=0"), true),
+        Encoding::Identity
+    );
+}
+
+#[test]
+fn serves_compressed_calendar() {
+    let folder = create_folder();
+    let request = IncomingRequest {
+        accept_encoding: Some("gzip".to_owned()),
+        ..get("/1337-secret.ics")
+    };
+    let reply = respond(folder.path(), &SyncHistory::default(), &request);
+    assert_eq!(reply.header("Content-Encoding"), Some("gzip"));
+    let mut decoded = String::new();
+    flate2::read::GzDecoder::new(reply.body.as_slice())
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, TEST_CALENDAR);
+
+    fs::write(
+        folder.path().join("1337-secret.ics.br"),
+        artifacts::brotli(TEST_CALENDAR.as_bytes()),
+    )
+    .unwrap();
+    let request = IncomingRequest {
+        accept_encoding: Some("br".to_owned()),
+        ..get("/1337-secret.ics")
+    };
+    let reply = respond(folder.path(), &SyncHistory::default(), &request);
+    assert_eq!(reply.header("Content-Encoding"), Some("br"));
+    let mut decoded = String::new();
+    brotli::Decompressor::new(reply.body.as_slice(), 4096)
+        .read_to_string(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded, TEST_CALENDAR);
+}
+
+#[test]
+fn serves_precompressed_artifacts_with_sidecar_etag() {
+    let folder = create_folder();
+    let mut metadata = Metadata::new(TEST_CALENDAR, &[]);
+    metadata.sha256 = "abc123".to_owned();
+    fs::write(
+        folder.path().join("1337-secret.ics.meta.json"),
+        serde_json::to_string(&metadata).unwrap(),
+    )
+    .unwrap();
+    // Differs from the calendar to tell it apart from compressing on demand
+    fs::write(
+        folder.path().join("1337-secret.ics.gz"),
+        artifacts::gzip(b"precompressed"),
+    )
+    .unwrap();
+
+    let request = IncomingRequest {
+        accept_encoding: Some("gzip, br".to_owned()),
+        ..get("/1337-secret.ics")
+    };
+    let reply = respond(folder.path(), &SyncHistory::default(), &request);
+    assert_eq!(reply.header("Content-Encoding"), Some("gzip"));
+    assert_eq!(reply.header("ETag"), Some("\"abc123-gzip\""));
+    assert_eq!(reply.body, artifacts::gzip(b"precompressed"));
+
+    let reply = respond(
+        folder.path(),
+        &SyncHistory::default(),
+        &get("/1337-secret.ics"),
+    );
+    assert_eq!(reply.header("ETag"), Some("\"abc123\""));
+    assert_eq!(reply.body, TEST_CALENDAR.as_bytes());
+}
+
+#[test]
+fn etag_without_sidecar_is_sha256() {
+    let folder = create_folder();
+    let reply = respond(
+        folder.path(),
+        &SyncHistory::default(),
+        &get("/1337-secret.ics"),
+    );
+    assert_eq!(
+        reply.header("ETag"),
+        Some(format!("\"{}\"", artifacts::sha256_hex(TEST_CALENDAR.as_bytes())).as_str())
+    );
+}
+
+#[test]
+fn serves_over_http() {
+    use std::io::Write as _;
+    use std::net::TcpStream;
+
+    let folder = create_folder();
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let address = server.server_addr().to_ip().unwrap();
+    let root = folder.path().to_path_buf();
+    std::thread::spawn(move || serve(&server, &root, &SyncHistory::default()));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream
+        .write_all(b"GET /1337-secret.ics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+    assert!(response.contains("text/calendar"), "{response}");
+    assert!(response.ends_with(TEST_CALENDAR), "{response}");
+}
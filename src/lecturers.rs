@@ -0,0 +1,113 @@
+//! Lecturers of the events from descriptions like `Dozent: HTM` and the teaching schedule of every lecturer: `calendars/lecturers/<lecturer>.ics`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context as _;
+
+use crate::changestatus::Changestatus;
+use crate::grouped_calendars::{Group, group_events, write_all_in};
+use crate::location::normalize_key;
+use crate::resolved_events::{ResolvedEvent, all_eventfile_events_in};
+use crate::{events, output_files};
+
+/// Subfolder of the calendars folder
+pub const SUBFOLDER: &str = "lecturers";
+
+/// Line prefixes of the descriptions naming the lecturers
+const PREFIXES: &[&str] = &["dozent", "dozentin", "dozenten", "lehrende", "lecturer"];
+
+/// Lecturer abbreviations of the lecturer field of the eventfile and of description lines like `Dozent: HTM` or `Dozenten: HTM, KSS`
+pub fn lecturers_of(lecturer: Option<&str>, description: &str) -> Vec<String> {
+    let mut result: Vec<String> = Vec::new();
+    let description_names = description.lines().filter_map(|line| {
+        let (prefix, names) = line.split_once(':')?;
+        let prefix = prefix.trim().to_lowercase();
+        PREFIXES.contains(&prefix.as_str()).then_some(names)
+    });
+    for names in lecturer.into_iter().chain(description_names) {
+        for name in names
+            .split([',', ';', '/', '&'])
+            .flat_map(|name| name.split(" und "))
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+        {
+            if !result.iter().any(|existing| existing == name) {
+                result.push(name.to_owned());
+            }
+        }
+    }
+    result
+}
+
+pub fn build_all() -> anyhow::Result<Vec<Changestatus>> {
+    build_all_in(
+        &Path::new(events::FOLDER).join("events"),
+        &Path::new(output_files::FOLDER).join(SUBFOLDER),
+    )
+}
+
+fn build_all_in(source: &Path, target: &Path) -> anyhow::Result<Vec<Changestatus>> {
+    let lecturers = collect_lecturers(&all_eventfile_events_in(source));
+    write_all_in(target, &lecturers).context("failed to write lecturer calendars")
+}
+
+/// Events of every lecturer by the key of the lecturer
+fn collect_lecturers(events: &[ResolvedEvent]) -> BTreeMap<String, Group> {
+    group_events(events, |event| {
+        event
+            .lecturers
+            .iter()
+            .map(|lecturer| (normalize_key(lecturer), lecturer.clone()))
+            .filter(|(key, _)| !key.is_empty())
+            .collect()
+    })
+}
+
+#[test]
+fn lecturers_of_examples() {
+    assert_eq!(lecturers_of(None, "Dozent: HTM"), ["HTM"]);
+    assert_eq!(
+        lecturers_of(None, "Dozenten: HTM, KSS / HTM und Schmidt"),
+        ["HTM", "KSS", "Schmidt"]
+    );
+    assert_eq!(
+        lecturers_of(None, "Praktikum\ndozentin:  KSS \n\nLaptop: mitbringen"),
+        ["KSS"]
+    );
+    assert!(lecturers_of(None, "").is_empty());
+    assert!(lecturers_of(None, "Dozent:").is_empty());
+    assert!(lecturers_of(None, "Dozent HTM").is_empty());
+    assert_eq!(
+        lecturers_of(Some("KSS, HTM"), "Dozent: HTM"),
+        ["KSS", "HTM"]
+    );
+}
+
+#[cfg(test)]
+const EVENTFILE: &str = r#"[
+    {"name": "BTI1-TI", "location": "1060", "description": "Dozent: HTM", "startTime": "2022-01-13T11:40:00", "endTime": "2022-01-13T12:00:00"},
+    {"name": "BTI1-TI", "location": "1060", "description": "Dozenten: HTM, KSS", "startTime": "2022-01-14T11:40:00", "endTime": "2022-01-14T12:00:00"},
+    {"name": "BTI1-TI", "location": "1060", "description": "", "startTime": "2022-01-15T11:40:00", "endTime": "2022-01-15T12:00:00"}
+]"#;
+
+#[test]
+fn builds_calendar_per_lecturer() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(source.join("BTI1-TI.json"), EVENTFILE).unwrap();
+    // Listed again in another eventfile
+    std::fs::write(source.join("BTI1.json"), EVENTFILE).unwrap();
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert_eq!(changes.len(), 2);
+
+    let content = std::fs::read_to_string(target.join("htm.ics")).unwrap();
+    assert_eq!(content.matches("BEGIN:VEVENT").count(), 2, "{content}");
+    assert!(content.contains("X-WR-CALNAME:@HAWHHCalendarBot (HTM)\r\n"));
+    let content = std::fs::read_to_string(target.join("kss.ics")).unwrap();
+    assert_eq!(content.matches("BEGIN:VEVENT").count(), 1, "{content}");
+    assert!(target.join(crate::grouped_calendars::INDEX).exists());
+}
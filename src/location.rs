@@ -0,0 +1,393 @@
+//! Structured view on the free text locations of the eventfiles and room changes like `BT7 R1.060` or `Raum 0.12 / Online`.
+
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::sync::OnceLock;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    pub rooms: Vec<Room>,
+    /// The event (also) takes place online
+    pub online: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Room {
+    pub building: Option<String>,
+    pub floor: Option<String>,
+    /// Room number within the building like `1.060`
+    pub number: String,
+}
+
+/// Address and coordinates of a building
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+pub struct Building {
+    pub address: String,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Buildings by their name as used in the locations like `BT7`
+pub type Buildings = HashMap<String, Building>;
+
+const ONLINE_WORDS: &[&str] = &["online", "zoom", "digital", "remote"];
+const ROOM_WORDS: &[&str] = &["raum", "room"];
+
+/// Buildings configured by the JSON file `BUILDINGS_FILE` (default `buildings.json`).
+/// Without the file no building is known.
+pub fn buildings() -> &'static Buildings {
+    static BUILDINGS: OnceLock<Buildings> = OnceLock::new();
+    BUILDINGS.get_or_init(|| {
+        let path = std::env::var("BUILDINGS_FILE").unwrap_or_else(|_| "buildings.json".to_owned());
+        load_buildings(&path)
+    })
+}
+
+fn load_buildings(path: &str) -> Buildings {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Buildings::new();
+    };
+    serde_json::from_str(&content).unwrap_or_else(|err| {
+        println!("ignore invalid buildings file {path}: {err}");
+        Buildings::new()
+    })
+}
+
+/// Rooms and whether the event is (also) online. Rooms are separated by `,`, `;` and `/`.
+/// Parts which are no room like `Labor Digitaltechnik 1.101` are kept as they are.
+pub fn parse_location(location: &str) -> Location {
+    let mut result = Location::default();
+    for part in location.split([',', ';']) {
+        // Links contain slashes which do not separate rooms
+        let mut words = Vec::new();
+        for word in part.split_whitespace() {
+            if word.contains("://") {
+                result.online = true;
+            } else {
+                words.push(word);
+            }
+        }
+        for part in words.join(" ").split('/') {
+            parse_part(part, &mut result);
+        }
+    }
+    result
+}
+
+fn parse_part(part: &str, result: &mut Location) {
+    let mut tokens = Vec::new();
+    for token in part.split_whitespace() {
+        if is_online_word(token) {
+            result.online = true;
+        } else if !ROOM_WORDS
+            .iter()
+            .any(|word| token.eq_ignore_ascii_case(word))
+        {
+            tokens.push(token);
+        }
+    }
+    if tokens.is_empty() {
+        return;
+    }
+    let room = parse_room(&tokens).unwrap_or_else(|| Room {
+        building: None,
+        floor: None,
+        number: part.split_whitespace().collect::<Vec<_>>().join(" "),
+    });
+    result.rooms.push(room);
+}
+
+/// Whole words only, `Digitaltechnik` is no online event
+fn is_online_word(token: &str) -> bool {
+    token
+        .split(|char: char| !char.is_alphanumeric())
+        .any(|word| {
+            ONLINE_WORDS
+                .iter()
+                .any(|online| word.eq_ignore_ascii_case(online))
+        })
+}
+
+/// Room of the tokens like `BT7 R1.060` or `1060`
+fn parse_room(tokens: &[&str]) -> Option<Room> {
+    let (building, number) = match tokens {
+        [number] => (None, *number),
+        [building, number] => (Some((*building).to_owned()), *number),
+        _ => return None,
+    };
+    // R1.060 is room 1.060
+    let number = match number.strip_prefix(['R', 'r']) {
+        Some(rest) if rest.starts_with(|char: char| char.is_ascii_digit()) => rest,
+        _ => number,
+    };
+    if !number.contains(|char: char| char.is_ascii_digit()) {
+        return None;
+    }
+
+    let floor = number
+        .split_once('.')
+        .map(|(floor, _)| floor)
+        .filter(|floor| !floor.is_empty())
+        .map(ToOwned::to_owned);
+
+    Some(Room {
+        building,
+        floor,
+        number: number.to_owned(),
+    })
+}
+
+impl Room {
+    /// Like `BT7 1.060`
+    pub fn name(&self) -> String {
+        self.building.as_ref().map_or_else(
+            || self.number.clone(),
+            |building| format!("{building} {}", self.number),
+        )
+    }
+
+    /// Filename safe identifier, same for every spelling of the room: `BT7 R1.060` → `bt7-1-060`
+    pub fn key(&self) -> String {
+        normalize_key(&self.name())
+    }
+
+    fn building_of<'buildings>(
+        &self,
+        buildings: &'buildings Buildings,
+    ) -> Option<&'buildings Building> {
+        let name = self.building.as_ref()?;
+        buildings
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, building)| building)
+    }
+}
+
+impl Location {
+    /// Cleaned location for calendars with the addresses of known buildings:
+    /// `BT7 1.060, Online (Berliner Tor 7, 20099 Hamburg)`
+    pub fn calendar_text(&self, buildings: &Buildings) -> String {
+        let mut parts = self.rooms.iter().map(Room::name).collect::<Vec<_>>();
+        if self.online {
+            parts.push("Online".to_owned());
+        }
+        let mut result = parts.join(", ");
+
+        let mut addresses: Vec<&str> = Vec::new();
+        for building in self
+            .rooms
+            .iter()
+            .filter_map(|room| room.building_of(buildings))
+        {
+            if !addresses.contains(&building.address.as_str()) {
+                addresses.push(&building.address);
+            }
+        }
+        if !addresses.is_empty() {
+            _ = write!(result, " ({})", addresses.join("; "));
+        }
+        result
+    }
+
+    /// Coordinates of the first room in a known building
+    pub fn geo(&self, buildings: &Buildings) -> Option<(f64, f64)> {
+        self.rooms
+            .iter()
+            .find_map(|room| room.building_of(buildings))
+            .map(|building| (building.latitude, building.longitude))
+    }
+}
+
+/// Cleaned location and coordinates of the raw location of an event for the calendar outputs.
+/// Without a room the link of the meeting is the location, so clients offer to join.
+pub fn for_calendar(
+    location: &str,
+    meeting: Option<&str>,
+    buildings: &Buildings,
+) -> (String, Option<(f64, f64)>) {
+    let location = parse_location(location);
+    let text = match meeting {
+        Some(url) if location.rooms.is_empty() => url.to_owned(),
+        _ => location.calendar_text(buildings),
+    };
+    (text, location.geo(buildings))
+}
+
+/// Lowercase alphanumeric parts joined by `-`: `Audimax 1` → `audimax-1`
+pub fn normalize_key(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for char in value.chars() {
+        if char.is_alphanumeric() {
+            result.extend(char.to_lowercase());
+        } else if !result.ends_with('-') {
+            result.push('-');
+        }
+    }
+    result.trim_matches('-').to_owned()
+}
+
+#[cfg(test)]
+fn room(building: Option<&str>, floor: Option<&str>, number: &str) -> Room {
+    Room {
+        building: building.map(ToOwned::to_owned),
+        floor: floor.map(ToOwned::to_owned),
+        number: number.to_owned(),
+    }
+}
+
+#[cfg(test)]
+pub fn test_buildings() -> Buildings {
+    let mut buildings = Buildings::new();
+    buildings.insert(
+        "BT7".to_owned(),
+        Building {
+            address: "Berliner Tor 7, 20099 Hamburg".to_owned(),
+            latitude: 53.5565,
+            longitude: 10.0225,
+        },
+    );
+    buildings
+}
+
+#[test]
+fn parse_location_examples() {
+    assert_eq!(
+        parse_location("1060"),
+        Location {
+            rooms: vec![room(None, None, "1060")],
+            online: false,
+        }
+    );
+    assert_eq!(
+        parse_location("BT7 R1.060"),
+        Location {
+            rooms: vec![room(Some("BT7"), Some("1"), "1.060")],
+            online: false,
+        }
+    );
+    assert_eq!(
+        parse_location("Raum 0.12 / Online"),
+        Location {
+            rooms: vec![room(None, Some("0"), "0.12")],
+            online: true,
+        }
+    );
+    assert_eq!(
+        parse_location("1060,  1061"),
+        Location {
+            rooms: vec![room(None, None, "1060"), room(None, None, "1061")],
+            online: false,
+        }
+    );
+    assert_eq!(
+        parse_location("BT7 0.12; Zoom"),
+        Location {
+            rooms: vec![room(Some("BT7"), Some("0"), "0.12")],
+            online: true,
+        }
+    );
+    assert_eq!(
+        parse_location("1060/1061"),
+        Location {
+            rooms: vec![room(None, None, "1060"), room(None, None, "1061")],
+            online: false,
+        }
+    );
+    assert_eq!(
+        parse_location("Online 1060"),
+        Location {
+            rooms: vec![room(None, None, "1060")],
+            online: true,
+        }
+    );
+    assert_eq!(
+        parse_location("Labor  Digitaltechnik 1.101 / Remote-Labor"),
+        Location {
+            rooms: vec![room(None, None, "Labor Digitaltechnik 1.101")],
+            online: true,
+        }
+    );
+    assert_eq!(
+        parse_location("Audimax"),
+        Location {
+            rooms: vec![room(None, None, "Audimax")],
+            online: false,
+        }
+    );
+    assert_eq!(
+        parse_location("https://bbb.haw-hamburg.de/b/htm-abc"),
+        Location {
+            rooms: Vec::new(),
+            online: true,
+        }
+    );
+    assert_eq!(parse_location(""), Location::default());
+    assert_eq!(parse_location("Raum"), Location::default());
+}
+
+#[test]
+fn key_is_the_same_for_every_spelling() {
+    let key = |location: &str| parse_location(location).rooms[0].key();
+    assert_eq!(key("BT7 R1.060"), "bt7-1-060");
+    assert_eq!(key("bt7  1.060"), "bt7-1-060");
+    assert_eq!(key("1060/1061"), "1060");
+    assert_eq!(key("Raum 1060"), "1060");
+}
+
+#[test]
+fn normalize_key_examples() {
+    assert_eq!(normalize_key("1060"), "1060");
+    assert_eq!(normalize_key("Audimax 1"), "audimax-1");
+    assert_eq!(normalize_key("BT7/0.12"), "bt7-0-12");
+    assert_eq!(normalize_key("(Hörsaal)"), "hörsaal");
+}
+
+#[test]
+fn calendar_text_examples() {
+    let buildings = test_buildings();
+    let text = |location: &str| parse_location(location).calendar_text(&buildings);
+    assert_eq!(text(""), "");
+    assert_eq!(text("1060, 1061"), "1060, 1061");
+    assert_eq!(text("Raum 0.12 / Online"), "0.12, Online");
+    assert_eq!(
+        text("BT7 R1.060, bt7 R1.061"),
+        "BT7 1.060, bt7 1.061 (Berliner Tor 7, 20099 Hamburg)"
+    );
+}
+
+#[test]
+fn geo_of_known_building() {
+    let buildings = test_buildings();
+    assert_eq!(
+        parse_location("1060, BT7 R1.060").geo(&buildings),
+        Some((53.5565, 10.0225))
+    );
+    assert_eq!(parse_location("1060").geo(&buildings), None);
+}
+
+#[test]
+fn buildings_file_format() {
+    let buildings: Buildings = serde_json::from_str(
+        r#"{"BT7": {"address": "Berliner Tor 7, 20099 Hamburg", "latitude": 53.5565, "longitude": 10.0225}}"#,
+    )
+    .unwrap();
+    assert_eq!(buildings, test_buildings());
+}
+
+#[test]
+fn buildings_file_is_loaded() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("buildings.json");
+    let path = path.to_str().unwrap();
+    assert!(load_buildings(path).is_empty());
+
+    std::fs::write(path, "not json").unwrap();
+    assert!(load_buildings(path).is_empty());
+
+    std::fs::write(
+        path,
+        r#"{"BT7": {"address": "Berliner Tor 7, 20099 Hamburg", "latitude": 53.5565, "longitude": 10.0225}}"#,
+    )
+    .unwrap();
+    assert_eq!(load_buildings(path), test_buildings());
+}
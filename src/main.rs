@@ -2,49 +2,112 @@ use std::thread::sleep;
 use std::time::{Duration, Instant};
 
 use crate::changestatus::{Changestatus, Changetype, write_change_summary};
+use crate::output_files::CleanupLimit;
 use crate::watchcat::Watchcat;
 
 mod apply_changes;
 mod apply_details;
+mod artifacts;
+mod berlin_time;
+mod busy_calendar;
+mod caldav;
 mod changestatus;
+mod cli;
+mod conflicts;
+mod eventfiles_guard;
 mod events;
+mod free_rooms;
+mod generate_agenda;
+mod generate_csv;
+mod generate_html;
 mod generate_ics;
+mod generate_jcal;
+mod generate_svg;
+mod generate_xcal;
+mod grouped_calendars;
+mod http_server;
+mod lecturers;
+mod location;
+mod meeting;
 mod output_files;
+mod public_calendars;
+mod resolved_events;
+mod room_calendars;
+mod timetable;
 mod userconfig;
 mod userconfigs;
 mod watchcat;
 
 fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if let Some(command) = args.first().filter(|arg| *arg != "--force-cleanup") {
+        if let Err(err) = cli::run(command, &args[1..]) {
+            eprintln!("{err:#}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     output_files::ensure_directory().expect("should be able to create output directory");
+    let force_cleanup = args.iter().any(|arg| arg == "--force-cleanup");
+
+    if let Ok(address) = std::env::var("HTTP_LISTEN") {
+        http_server::spawn(&address, output_files::FOLDER.into())
+            .expect("should be able to start the http server");
+        println!(
+            "Serve calendars on http://{address}/ and CalDAV on http://{address}/caldav/<calendar>/"
+        );
+    }
     let mut stdout = std::io::stdout();
 
     println!("Pull eventfiles...");
-    events::pull();
-    println!("Begin build all configs...");
+    let eventfiles_guard = eventfiles_guard::Guard::from_env();
+    let mut eventfiles = events::PullStatus::default();
+    pull_eventfiles(&mut eventfiles, &eventfiles_guard);
 
-    let changes = output_files::all_remove_rest(userconfigs::load_all())
+    if events::checkout_exists() {
+        println!("Begin build all configs...");
+        let changes = output_files::all_remove_rest(
+            userconfigs::load_all(),
+            CleanupLimit::from_env(force_cleanup),
+        )
         .expect("should be able to build all initial userconfigs");
-    _ = write_change_summary(&mut stdout, changes, Changetype::ALL);
+        _ = write_change_summary(&mut stdout, changes, Changetype::ALL);
+        println!("Finished building all configs.");
+        build_shared_calendars(&mut stdout, Changetype::ALL);
+    } else {
+        println!("No eventfiles checkout. Skip building all configs.");
+    }
 
-    println!("Finished building all configs. Engage watchcats...\n");
+    println!("Engage watchcats...\n");
     let mut last_eventfiles_pull = Instant::now();
     let userconfig_watcher = Watchcat::new(userconfigs::FOLDER);
 
     loop {
-        if last_eventfiles_pull.elapsed() > Duration::from_mins(42) {
+        if last_eventfiles_pull.elapsed() > Duration::from_mins(42) || eventfiles.retry_due() {
             println!("\nPull eventfiles...");
-            events::pull();
+            pull_eventfiles(&mut eventfiles, &eventfiles_guard);
+            last_eventfiles_pull = Instant::now();
+            if !events::checkout_exists() {
+                println!("No eventfiles checkout. Skip building all configs.\n");
+                continue;
+            }
+
             println!("Begin build all configs...");
 
-            match output_files::all_remove_rest(userconfigs::load_all()) {
+            match output_files::all_remove_rest(
+                userconfigs::load_all(),
+                CleanupLimit::from_env(false),
+            ) {
                 Ok(changes) => {
                     _ = write_change_summary(&mut stdout, changes, Changetype::INTERESTING);
                 }
                 Err(err) => println!("failed to build all {err:#}"),
             }
 
-            println!("Finished building all configs.\n");
-            last_eventfiles_pull = Instant::now();
+            println!("Finished building all configs.");
+            build_shared_calendars(&mut stdout, Changetype::INTERESTING);
+            println!();
         }
 
         for filename in userconfig_watcher.get_changed_filenames() {
@@ -59,7 +122,43 @@ fn main() {
     }
 }
 
+fn pull_eventfiles(status: &mut events::PullStatus, guard: &eventfiles_guard::Guard) {
+    if let Err(err) = status.pull(guard) {
+        println!("failed to pull eventfiles, keep the last checkout: {err:#}");
+    }
+    println!("{status}");
+}
+
+/// Calendars based on the eventfiles only, not on any userconfig
+fn build_shared_calendars(stdout: &mut std::io::Stdout, to_be_shown: &[Changetype]) {
+    println!("Begin build public calendars...");
+    match public_calendars::build_all() {
+        Ok(changes) => _ = write_change_summary(stdout, changes, to_be_shown),
+        Err(err) => println!("failed to build public calendars {err:#}"),
+    }
+    println!("Begin build room calendars...");
+    match room_calendars::build_all() {
+        Ok(changes) => _ = write_change_summary(stdout, changes, to_be_shown),
+        Err(err) => println!("failed to build room calendars {err:#}"),
+    }
+    if let Err(err) = free_rooms::build_all() {
+        println!("failed to build free rooms index {err:#}");
+    }
+    println!("Begin build lecturer calendars...");
+    match lecturers::build_all() {
+        Ok(changes) => _ = write_change_summary(stdout, changes, to_be_shown),
+        Err(err) => println!("failed to build lecturer calendars {err:#}"),
+    }
+    println!("Finished building public, room and lecturer calendars.");
+}
+
+/// Without eventfiles every event seems gone, which would remove the calendar.
+/// The next build of all configs after a successful pull includes the change anyway.
 fn do_specific(userconfig_filename: &str) -> anyhow::Result<Changestatus> {
+    anyhow::ensure!(
+        events::checkout_exists(),
+        "No eventfiles checkout. Keep the existing calendar."
+    );
     let config = userconfigs::load_specific(userconfig_filename)?;
     output_files::one(config)
 }
@@ -0,0 +1,144 @@
+//! Links to online meetings like Zoom or `BigBlueButton` within the location or description of an event.
+
+use crate::generate_ics::SoonToBeIcsEvent;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Meeting {
+    pub url: String,
+    /// Name of the meeting service like `Zoom`
+    pub label: &'static str,
+}
+
+/// Meetings of the event. The description contains the notes of the user too.
+pub fn meetings_of(event: &SoonToBeIcsEvent) -> Vec<Meeting> {
+    let mut result: Vec<Meeting> = Vec::new();
+    for url in event
+        .meta
+        .url
+        .iter()
+        .flat_map(|url| urls_in(url))
+        .chain(urls_in(&event.location))
+        .chain(urls_in(&event.description))
+    {
+        if result.iter().any(|meeting| meeting.url == url) {
+            continue;
+        }
+        if let Some(label) = label_of(url) {
+            result.push(Meeting {
+                url: url.to_owned(),
+                label,
+            });
+        }
+    }
+    result
+}
+
+fn urls_in(text: &str) -> Vec<&str> {
+    let mut result = Vec::new();
+    let mut rest = text;
+    while let Some(index) = rest.find("http") {
+        rest = &rest[index..];
+        if !rest.starts_with("https://") && !rest.starts_with("http://") {
+            rest = &rest[4..];
+            continue;
+        }
+        let end = rest
+            .find(|char: char| char.is_whitespace() || matches!(char, '<' | '>' | '"'))
+            .unwrap_or(rest.len());
+        // Punctuation of the surrounding sentence
+        result.push(rest[..end].trim_end_matches(['.', ',', ';', ':', ')', '!', '?']));
+        rest = &rest[end..];
+    }
+    result
+}
+
+fn label_of(url: &str) -> Option<&'static str> {
+    let host = url
+        .split_once("://")?
+        .1
+        .split(['/', ':', '?', '#'])
+        .next()?
+        .to_lowercase();
+    if host == "zoom.us" || host.ends_with(".zoom.us") {
+        Some("Zoom")
+    } else if host.starts_with("bbb.") || host.contains("bigbluebutton") {
+        Some("BigBlueButton")
+    } else if host == "teams.microsoft.com" || host == "teams.live.com" {
+        Some("Microsoft Teams")
+    } else if host == "webex.com" || host.ends_with(".webex.com") {
+        Some("Webex")
+    } else if host == "meet.google.com" {
+        Some("Google Meet")
+    } else if host == "meet.jit.si" || host.contains("jitsi") {
+        Some("Jitsi")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+fn create_event(location: &str, description: &str) -> SoonToBeIcsEvent {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap();
+    SoonToBeIcsEvent {
+        name: "BTI5-VS".to_owned(),
+        start_time,
+        end_time: start_time,
+        description: description.to_owned(),
+        location: location.to_owned(),
+        ..SoonToBeIcsEvent::default()
+    }
+}
+
+#[test]
+fn urls_in_examples() {
+    assert_eq!(
+        urls_in("Zoom: https://haw-hamburg.zoom.us/j/123?pwd=abc. Bis gleich!"),
+        ["https://haw-hamburg.zoom.us/j/123?pwd=abc"]
+    );
+    assert_eq!(
+        urls_in("(http://a.de/x) https://b.de\nhttpd"),
+        ["http://a.de/x", "https://b.de"]
+    );
+    assert!(urls_in("Dozent: HTM").is_empty());
+}
+
+#[test]
+fn label_of_examples() {
+    assert_eq!(label_of("https://haw-hamburg.zoom.us/j/123"), Some("Zoom"));
+    assert_eq!(
+        label_of("https://bbb.informatik.haw-hamburg.de/b/htm-abc"),
+        Some("BigBlueButton")
+    );
+    assert_eq!(
+        label_of("https://teams.microsoft.com/l/meetup-join/abc"),
+        Some("Microsoft Teams")
+    );
+    assert_eq!(label_of("https://meet.jit.si/BTI5-VS"), Some("Jitsi"));
+    assert_eq!(label_of("https://zoom.us.example.com/j/123"), None);
+    assert_eq!(label_of("https://www.haw-hamburg.de"), None);
+}
+
+#[test]
+fn meetings_of_location_and_description() {
+    let event = create_event(
+        "https://bbb.haw-hamburg.de/b/htm",
+        "Dozent: HTM\n\nSkript: https://www.haw-hamburg.de/vs\nFragestunde: https://haw-hamburg.zoom.us/j/1\nhttps://bbb.haw-hamburg.de/b/htm",
+    );
+    assert_eq!(
+        meetings_of(&event),
+        [
+            Meeting {
+                url: "https://bbb.haw-hamburg.de/b/htm".to_owned(),
+                label: "BigBlueButton",
+            },
+            Meeting {
+                url: "https://haw-hamburg.zoom.us/j/1".to_owned(),
+                label: "Zoom",
+            },
+        ]
+    );
+    assert!(meetings_of(&create_event("1060", "Dozent: HTM")).is_empty());
+}
@@ -1,21 +1,31 @@
-use std::fs;
+use std::fs::{self, File};
+use std::io::Write as _;
 use std::path::Path;
 
 use anyhow::Context as _;
 
-use crate::apply_changes::apply_change;
-use crate::apply_details::apply_details;
+use crate::artifacts::{self, Metadata};
+use crate::busy_calendar::{self, generate_busy_ics};
 use crate::changestatus::{Changestatus, Changetype};
-use crate::events;
+use crate::conflicts::find_conflicts;
+use crate::generate_html::generate_html;
 use crate::generate_ics::{SoonToBeIcsEvent, generate_ics};
-use crate::userconfig::{EventDetails, RemovedEvents, UserconfigFile};
+use crate::generate_jcal::generate_jcal;
+use crate::generate_xcal::generate_xcal;
+use crate::resolved_events::{ResolvedEvent, resolve_events};
+use crate::userconfig::UserconfigFile;
+use crate::userconfigs::Loaded;
 
 pub struct Buildresult {
     pub changestatus: Changestatus,
-    pub filename: String,
+    /// Filename without extension shared by the calendar and its artifacts
+    pub stem: String,
+    /// Filename without extension of the busy calendar when one was written
+    pub busy_stem: Option<String>,
 }
 
 pub const FOLDER: &str = "calendars";
+const TEMP_SUFFIX: &str = ".tmp";
 
 pub fn ensure_directory() -> std::io::Result<()> {
     fs::create_dir_all(FOLDER)
@@ -31,54 +41,34 @@ pub fn one(content: UserconfigFile) -> anyhow::Result<Changestatus> {
 fn one_internal(content: UserconfigFile) -> anyhow::Result<Buildresult> {
     let user_id = content.chat.id;
     let first_name = content.chat.first_name;
-    let ics_filename = format!("{user_id}-{}.ics", content.config.calendarfile_suffix);
-    let path = Path::new(FOLDER).join(&ics_filename);
+    let stem = format!("{user_id}-{}", content.config.calendarfile_suffix);
+    let busy_stem = content
+        .config
+        .busy_calendarfile_suffix
+        .as_ref()
+        .map(|suffix| format!("{user_id}-{suffix}"));
+    let folder = Path::new(FOLDER);
+    let busy_folder = folder.join(busy_calendar::SUBFOLDER);
+    let path = folder.join(format!("{stem}{}", artifacts::ICS));
 
-    let mut changetype = Changetype::Same;
-
-    let existing = get_existing_files(&format!("{user_id}-"))
-        .context("failed to read existing calendars of user")?;
-
-    match existing.len() {
-        1 => {
-            if existing[0] != ics_filename {
-                let existing_path = Path::new(FOLDER).join(&existing[0]);
-                fs::rename(existing_path, &path).context("failed to rename old calendar")?;
-                changetype = Changetype::Moved;
-            }
-        }
-        0 => {}
-        _ => {
-            for filename in existing {
-                let existing_path = Path::new(FOLDER).join(filename);
-                fs::remove_file(existing_path)
-                    .context("failed to remove superfluous calendars of user")?;
-                changetype = Changetype::Removed;
-            }
-        }
-    }
+    let mut changetype = adopt_existing_calendars(folder, user_id, &stem)?;
 
-    let mut user_events = Vec::new();
-    for (filename, details) in content.config.events {
-        match load_and_parse_events(&filename, details, content.config.removed_events) {
-            Ok(mut events) => user_events.append(&mut events),
-            Err(_) if filename.contains(char::is_uppercase) => {
-                // TODO remove ignore legacy filenames
-            }
-            Err(err) => println!("skip eventfile {filename:>12}.json: {err:#}"),
-        }
-    }
+    let resolved = resolve_events(content.config);
 
-    if user_events.is_empty() {
+    if resolved.is_empty() {
         if path.exists() {
-            fs::remove_file(&path).context("failed to remove calendar with now 0 events")?;
+            remove_artifacts(folder, &stem)
+                .context("failed to remove calendar with now 0 events")?;
             changetype = Changetype::Removed;
         } else {
             changetype = Changetype::Skipped;
         }
+        write_busy_calendar(&busy_folder, user_id, None, &[])
+            .context("failed to remove busy calendar")?;
 
         return Ok(Buildresult {
-            filename: ics_filename,
+            stem,
+            busy_stem: None,
             changestatus: Changestatus {
                 name: first_name,
                 changetype,
@@ -86,23 +76,48 @@ fn one_internal(content: UserconfigFile) -> anyhow::Result<Buildresult> {
         });
     }
 
-    user_events.sort_by_cached_key(|event| event.start_time);
+    let user_events = resolved
+        .iter()
+        .map(|resolved| resolved.event.clone())
+        .collect::<Vec<_>>();
     let ics_content = generate_ics(&first_name, &user_events);
+    let metadata = Metadata::new(&ics_content, &user_events);
 
-    if let Ok(current_content) = fs::read_to_string(&path) {
-        if ics_content != current_content {
+    if path.exists() {
+        // The sidecar hash saves reading and comparing the whole old calendar
+        let is_same = Metadata::read(&folder.join(format!("{stem}{}", artifacts::METADATA)))
+            .map_or_else(
+                || fs::read_to_string(&path).is_ok_and(|current| current == ics_content),
+                |current| current.sha256 == metadata.sha256,
+            );
+        if !is_same {
             changetype = Changetype::Changed;
         }
     } else {
         changetype = Changetype::Added;
     }
 
-    if matches!(changetype, Changetype::Changed | Changetype::Added) {
-        fs::write(&path, &ics_content).context("failed to write ics file content")?;
+    if matches!(changetype, Changetype::Changed | Changetype::Added)
+        || !are_artifacts_complete(folder, &stem)
+    {
+        write_artifacts(
+            folder,
+            &stem,
+            &first_name,
+            &user_events,
+            &resolved,
+            &ics_content,
+            &metadata,
+        )
+        .context("failed to write ics file content")?;
     }
 
+    write_busy_calendar(&busy_folder, user_id, busy_stem.as_deref(), &user_events)
+        .context("failed to write busy calendar")?;
+
     Ok(Buildresult {
-        filename: ics_filename,
+        stem,
+        busy_stem,
         changestatus: Changestatus {
             name: first_name,
             changetype,
@@ -110,46 +125,283 @@ fn one_internal(content: UserconfigFile) -> anyhow::Result<Buildresult> {
     })
 }
 
-fn load_and_parse_events(
-    event_filename: &str,
-    details: EventDetails,
-    removed_events: RemovedEvents,
-) -> anyhow::Result<Vec<SoonToBeIcsEvent>> {
-    let mut result = Vec::new();
-    for event in events::read(event_filename)? {
-        let mut event = event.into();
-        apply_details(&mut event, &details);
-        result.push(event);
+/// Rename the calendar of the user to the current stem or remove superfluous ones
+fn adopt_existing_calendars(folder: &Path, user_id: i64, stem: &str) -> anyhow::Result<Changetype> {
+    let mut changetype = Changetype::Same;
+    let existing = get_existing_stems(&format!("{user_id}-"))
+        .context("failed to read existing calendars of user")?;
+
+    match existing.len() {
+        1 => {
+            if existing[0] != stem {
+                for extension in artifacts::EXTENSIONS {
+                    let existing_path = folder.join(format!("{}{extension}", existing[0]));
+                    if existing_path.exists() {
+                        fs::rename(existing_path, folder.join(format!("{stem}{extension}")))
+                            .context("failed to rename old calendar")?;
+                    }
+                }
+                sync_directory(folder).context("failed to sync renamed calendar")?;
+                changetype = Changetype::Moved;
+            }
+        }
+        0 => {}
+        _ => {
+            for filename in get_existing_files(&format!("{user_id}-"))
+                .context("failed to read existing calendars of user")?
+            {
+                let existing_path = folder.join(filename);
+                fs::remove_file(existing_path)
+                    .context("failed to remove superfluous calendars of user")?;
+                changetype = Changetype::Removed;
+            }
+        }
+    }
+    Ok(changetype)
+}
+
+/// Write the calendar, its precompressed variants, other formats, the conflict report and the metadata sidecar.
+///
+/// The sidecar is written last. Its hash only matches once everything else is in place.
+fn write_artifacts(
+    folder: &Path,
+    stem: &str,
+    calendarname: &str,
+    events: &[SoonToBeIcsEvent],
+    resolved: &[ResolvedEvent],
+    ics_content: &str,
+    metadata: &Metadata,
+) -> anyhow::Result<()> {
+    let path_of = |extension: &str| folder.join(format!("{stem}{extension}"));
+
+    write_atomic(&path_of(artifacts::ICS), ics_content.as_bytes())?;
+    write_atomic(
+        &path_of(artifacts::GZIP),
+        &artifacts::gzip(ics_content.as_bytes()),
+    )?;
+    if artifacts::brotli_enabled() {
+        write_atomic(
+            &path_of(artifacts::BROTLI),
+            &artifacts::brotli(ics_content.as_bytes()),
+        )?;
+    } else if path_of(artifacts::BROTLI).exists() {
+        // Do not leave an outdated variant behind
+        fs::remove_file(path_of(artifacts::BROTLI))?;
     }
-    for (date, change) in details.changes {
-        apply_change(&mut result, date, change, removed_events);
+    if artifacts::jcal_enabled() {
+        write_atomic(
+            &path_of(artifacts::JCAL),
+            generate_jcal(calendarname, events).as_bytes(),
+        )?;
+    } else if path_of(artifacts::JCAL).exists() {
+        fs::remove_file(path_of(artifacts::JCAL))?;
     }
-    Ok(result)
+    if artifacts::xcal_enabled() {
+        write_atomic(
+            &path_of(artifacts::XCAL),
+            generate_xcal(calendarname, events).as_bytes(),
+        )?;
+    } else if path_of(artifacts::XCAL).exists() {
+        fs::remove_file(path_of(artifacts::XCAL))?;
+    }
+    if artifacts::html_enabled() {
+        write_atomic(
+            &path_of(artifacts::HTML),
+            generate_html(calendarname, resolved).as_bytes(),
+        )?;
+    } else if path_of(artifacts::HTML).exists() {
+        fs::remove_file(path_of(artifacts::HTML))?;
+    }
+    write_atomic(
+        &path_of(artifacts::CONFLICTS),
+        serde_json::to_string_pretty(&find_conflicts(resolved))?.as_bytes(),
+    )?;
+    write_atomic(
+        &path_of(artifacts::METADATA),
+        serde_json::to_string_pretty(metadata)?.as_bytes(),
+    )?;
+    Ok(())
+}
+
+/// Write the busy calendar of the user when it has a stem and remove every other busy calendar of the user
+fn write_busy_calendar(
+    folder: &Path,
+    user_id: i64,
+    stem: Option<&str>,
+    events: &[SoonToBeIcsEvent],
+) -> anyhow::Result<()> {
+    fs::create_dir_all(folder).context("failed to create busy folder")?;
+    for filename in get_existing_files_in(folder, &format!("{user_id}-"))? {
+        if artifacts::stem(&filename) != stem {
+            fs::remove_file(folder.join(&filename))
+                .with_context(|| format!("failed to remove busy calendar {filename}"))?;
+        }
+    }
+
+    let Some(stem) = stem else {
+        return Ok(());
+    };
+    let path = folder.join(format!("{stem}{}", artifacts::ICS));
+    let content = generate_busy_ics(events);
+    if fs::read_to_string(&path).is_ok_and(|existing| existing == content) {
+        return Ok(());
+    }
+    write_atomic(&path, content.as_bytes())?;
+    Ok(())
+}
+
+fn are_artifacts_complete(folder: &Path, stem: &str) -> bool {
+    let exists = |extension: &str| folder.join(format!("{stem}{extension}")).exists();
+    exists(artifacts::GZIP)
+        && exists(artifacts::METADATA)
+        && exists(artifacts::CONFLICTS)
+        && exists(artifacts::BROTLI) == artifacts::brotli_enabled()
+        && exists(artifacts::JCAL) == artifacts::jcal_enabled()
+        && exists(artifacts::XCAL) == artifacts::xcal_enabled()
+        && exists(artifacts::HTML) == artifacts::html_enabled()
 }
 
-pub fn all_remove_rest(list: Vec<UserconfigFile>) -> anyhow::Result<Vec<Changestatus>> {
+fn remove_artifacts(folder: &Path, stem: &str) -> std::io::Result<()> {
+    for extension in artifacts::EXTENSIONS {
+        let path = folder.join(format!("{stem}{extension}"));
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Limits how many superfluous calendars a cleanup may remove at once.
+///
+/// When the userconfigs are missing (volume mounted late or empty) every calendar seems superfluous.
+/// Aborting the cleanup then keeps the calendars of all users.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanupLimit {
+    Calendars(usize),
+    Percent(u8),
+    /// Intentional purges remove everything superfluous
+    Forced,
+}
+
+impl CleanupLimit {
+    const DEFAULT: Self = Self::Percent(20);
+    /// Percentages always allow this many calendars to be removed, small deployments could not remove anything otherwise
+    const MIN_CALENDARS: usize = 3;
+
+    /// `CALENDARS_CLEANUP_LIMIT` is either a number of calendars (`25`) or a percentage of existing calendars (`20%`).
+    /// A calendar counts once no matter how many artifacts it has.
+    pub fn from_env(forced: bool) -> Self {
+        if forced {
+            return Self::Forced;
+        }
+        std::env::var("CALENDARS_CLEANUP_LIMIT")
+            .ok()
+            .and_then(|value| Self::parse(&value))
+            .unwrap_or(Self::DEFAULT)
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        value.strip_suffix('%').map_or_else(
+            || value.parse().ok().map(Self::Calendars),
+            |percent| percent.trim().parse().ok().map(Self::Percent),
+        )
+    }
+
+    const fn allows(self, to_be_removed: usize, existing: usize) -> bool {
+        match self {
+            Self::Calendars(max) => to_be_removed <= max,
+            Self::Percent(max) => {
+                to_be_removed <= Self::MIN_CALENDARS
+                    || to_be_removed * 100 <= existing * max as usize
+            }
+            Self::Forced => true,
+        }
+    }
+}
+
+pub fn all_remove_rest(
+    userconfigs: Loaded,
+    cleanup_limit: CleanupLimit,
+) -> anyhow::Result<Vec<Changestatus>> {
     let mut changestati: Vec<Changestatus> = Vec::new();
-    let mut created_files: Vec<String> = Vec::new();
+    let mut created_stems: Vec<String> = Vec::new();
+    let mut busy_stems: Vec<String> = Vec::new();
+    let mut failed_chats = userconfigs.failed;
+    let busy_folder = Path::new(FOLDER).join(busy_calendar::SUBFOLDER);
 
-    for content in list {
+    for content in userconfigs.successful {
         let chat_id = content.chat.id;
         match one_internal(content) {
             Ok(filechange) => {
                 changestati.push(filechange.changestatus);
-                created_files.push(filechange.filename);
+                created_stems.push(filechange.stem);
+                busy_stems.extend(filechange.busy_stem);
+            }
+            Err(error) => {
+                println!("Failed to build calendar for {chat_id}: {error:#}");
+                failed_chats.push(chat_id);
             }
-            Err(error) => println!("Failed to build calendar for {chat_id}: {error:#}"),
         }
     }
 
-    let existing = get_existing_files("").context("failed to read calendars dir for cleanup")?;
-
-    for filename in existing {
-        if created_files.contains(&filename) {
-            continue;
+    // Keep the last good calendars of failed chats instead of removing them as superfluous
+    for chat_id in failed_chats {
+        let mut existing = get_existing_stems(&format!("{chat_id}-"))
+            .context("failed to read existing calendars of failed user")?;
+        created_stems.append(&mut existing);
+        if busy_folder.exists() {
+            let mut existing = get_existing_stems_in(&busy_folder, &format!("{chat_id}-"))
+                .context("failed to read existing busy calendars of failed user")?;
+            busy_stems.append(&mut existing);
         }
+        changestati.push(Changestatus {
+            name: chat_id.to_string(),
+            changetype: Changetype::Failed,
+        });
+    }
+
+    let mut removed = remove_rest(Path::new(FOLDER), &created_stems, cleanup_limit)?;
+    changestati.append(&mut removed);
+    if busy_folder.exists() {
+        let mut removed = remove_rest(&busy_folder, &busy_stems, cleanup_limit)?;
+        changestati.append(&mut removed);
+    }
+    Ok(changestati)
+}
+
+fn remove_rest(
+    folder: &Path,
+    created_stems: &[String],
+    cleanup_limit: CleanupLimit,
+) -> anyhow::Result<Vec<Changestatus>> {
+    let existing =
+        get_existing_files_in(folder, "").context("failed to read calendars dir for cleanup")?;
+    let existing_amount = count_calendars(&existing);
+    let superfluous = existing
+        .into_iter()
+        .filter(|filename| {
+            artifacts::stem(filename)
+                .is_none_or(|stem| !created_stems.iter().any(|created| created == stem))
+        })
+        .collect::<Vec<_>>();
 
-        let path = Path::new(FOLDER).join(&filename);
+    // Start with --force-cleanup to remove them anyway
+    if !cleanup_limit.allows(count_calendars(&superfluous), existing_amount) {
+        return Ok(superfluous
+            .into_iter()
+            .map(|filename| Changestatus {
+                name: filename,
+                changetype: Changetype::Aborted,
+            })
+            .collect());
+    }
+
+    remove_leftover_temp_files(folder).context("failed to remove leftover temporary files")?;
+
+    let mut changestati: Vec<Changestatus> = Vec::new();
+    for filename in superfluous {
+        let path = folder.join(&filename);
         fs::remove_file(path)
             .with_context(|| format!("failed to remove superfluous calendar file {filename}"))?;
 
@@ -162,18 +414,351 @@ pub fn all_remove_rest(list: Vec<UserconfigFile>) -> anyhow::Result<Vec<Changest
     Ok(changestati)
 }
 
+/// Distinct calendars of the files. Files without a known artifact extension count on their own.
+fn count_calendars(filenames: &[String]) -> usize {
+    let mut calendars = filenames
+        .iter()
+        .map(|filename| artifacts::stem(filename).unwrap_or(filename))
+        .collect::<Vec<_>>();
+    calendars.sort_unstable();
+    calendars.dedup();
+    calendars.len()
+}
+
+/// Calendars of which at least one artifact exists
+fn get_existing_stems(starts_with: &str) -> std::io::Result<Vec<String>> {
+    get_existing_stems_in(Path::new(FOLDER), starts_with)
+}
+
+fn get_existing_stems_in(folder: &Path, starts_with: &str) -> std::io::Result<Vec<String>> {
+    let mut stems = get_existing_files_in(folder, starts_with)?
+        .iter()
+        .filter_map(|filename| artifacts::stem(filename))
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    stems.sort();
+    stems.dedup();
+    Ok(stems)
+}
+
 fn get_existing_files(starts_with: &str) -> std::io::Result<Vec<String>> {
+    get_existing_files_in(Path::new(FOLDER), starts_with)
+}
+
+fn get_existing_files_in(folder: &Path, starts_with: &str) -> std::io::Result<Vec<String>> {
     let mut list: Vec<String> = Vec::new();
-    for maybe_entry in fs::read_dir(FOLDER)? {
-        let filename = maybe_entry?
+    for maybe_entry in fs::read_dir(folder)? {
+        let entry = maybe_entry?;
+        // Subfolders like the public calendars are not calendars of users
+        if entry.file_type()?.is_dir() {
+            continue;
+        }
+        let filename = entry
             .file_name()
             .into_string()
             .expect("filename should be UTF8");
 
-        if filename.starts_with(starts_with) {
+        if filename.starts_with(starts_with) && !filename.starts_with('.') {
             list.push(filename);
         }
     }
 
     Ok(list)
 }
+
+/// Write the file so readers only ever see the old or the new content completely.
+///
+/// The content is written to a hidden temporary file in the same directory, synced to disk and
+/// renamed over the target. A crash leaves at most the temporary file behind.
+pub fn write_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let folder = path.parent().unwrap_or_else(|| Path::new("."));
+    let filename = path
+        .file_name()
+        .and_then(std::ffi::OsStr::to_str)
+        .expect("path should have a UTF8 filename");
+    let temp_path = folder.join(format!(".{filename}{TEMP_SUFFIX}"));
+
+    let written = File::create(&temp_path).and_then(|mut file| {
+        file.write_all(content)?;
+        file.sync_all()
+    });
+    if let Err(err) = written {
+        _ = fs::remove_file(&temp_path);
+        return Err(err);
+    }
+
+    fs::rename(&temp_path, path)?;
+    sync_directory(folder)
+}
+
+/// Persist renames within the directory
+fn sync_directory(folder: &Path) -> std::io::Result<()> {
+    File::open(folder)?.sync_all()
+}
+
+fn remove_leftover_temp_files(folder: &Path) -> std::io::Result<()> {
+    for maybe_entry in fs::read_dir(folder)? {
+        let entry = maybe_entry?;
+        let is_temp = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|filename| filename.starts_with('.') && filename.ends_with(TEMP_SUFFIX));
+        if is_temp {
+            fs::remove_file(entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn create_calendars(folder: &Path, amount: usize) -> Vec<String> {
+    let stems = (0..amount)
+        .map(|i| format!("{i}-secret"))
+        .collect::<Vec<_>>();
+    for stem in &stems {
+        fs::write(folder.join(format!("{stem}.ics")), "BEGIN:VCALENDAR").unwrap();
+    }
+    stems
+}
+
+/// Calendars with every artifact like a deployment with all formats enabled
+#[cfg(test)]
+fn create_calendars_with_artifacts(folder: &Path, amount: usize) -> Vec<String> {
+    let stems = create_calendars(folder, amount);
+    for stem in &stems {
+        for extension in artifacts::EXTENSIONS {
+            fs::write(folder.join(format!("{stem}{extension}")), "").unwrap();
+        }
+    }
+    stems
+}
+
+#[test]
+fn cleanup_limit_parse_examples() {
+    assert_eq!(CleanupLimit::parse("25"), Some(CleanupLimit::Calendars(25)));
+    assert_eq!(CleanupLimit::parse("10%"), Some(CleanupLimit::Percent(10)));
+    assert_eq!(CleanupLimit::parse(" 5 % "), Some(CleanupLimit::Percent(5)));
+    assert_eq!(CleanupLimit::parse("many"), None);
+}
+
+#[test]
+fn cleanup_removes_superfluous_within_limit() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stems = create_calendars(tempdir.path(), 10);
+
+    let changes = remove_rest(tempdir.path(), &stems[1..], CleanupLimit::Percent(10)).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].name, "0-secret.ics");
+    assert_eq!(changes[0].changetype, Changetype::Removed);
+    assert!(!tempdir.path().join("0-secret.ics").exists());
+    assert!(tempdir.path().join("1-secret.ics").exists());
+}
+
+#[test]
+fn cleanup_aborts_above_percentage() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stems = create_calendars(tempdir.path(), 40);
+
+    let changes = remove_rest(tempdir.path(), &stems[5..], CleanupLimit::Percent(10)).unwrap();
+    assert_eq!(changes.len(), 5);
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == Changetype::Aborted)
+    );
+    assert!(tempdir.path().join("0-secret.ics").exists());
+    assert!(tempdir.path().join("4-secret.ics").exists());
+}
+
+#[test]
+fn small_cleanups_are_allowed_by_percentage() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stems = create_calendars_with_artifacts(tempdir.path(), 3);
+
+    let changes = remove_rest(tempdir.path(), &stems[1..], CleanupLimit::DEFAULT).unwrap();
+    assert_eq!(changes.len(), artifacts::EXTENSIONS.len());
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == Changetype::Removed)
+    );
+    assert!(!tempdir.path().join("0-secret.ics").exists());
+    assert!(tempdir.path().join("1-secret.ics.gz").exists());
+}
+
+#[test]
+fn cleanup_limit_counts_calendars_not_artifacts() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stems = create_calendars_with_artifacts(tempdir.path(), 10);
+
+    let changes = remove_rest(tempdir.path(), &stems[1..], CleanupLimit::Calendars(3)).unwrap();
+    assert_eq!(changes.len(), artifacts::EXTENSIONS.len());
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == Changetype::Removed)
+    );
+
+    let changes = remove_rest(tempdir.path(), &stems[5..], CleanupLimit::Calendars(3)).unwrap();
+    assert_eq!(changes.len(), 4 * artifacts::EXTENSIONS.len());
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == Changetype::Aborted)
+    );
+}
+
+#[test]
+fn cleanup_aborts_above_absolute_amount() {
+    let tempdir = tempfile::tempdir().unwrap();
+    create_calendars(tempdir.path(), 10);
+
+    let changes = remove_rest(tempdir.path(), &[], CleanupLimit::Calendars(3)).unwrap();
+    assert_eq!(changes.len(), 10);
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == Changetype::Aborted)
+    );
+    assert_eq!(get_existing_files_in(tempdir.path(), "").unwrap().len(), 10);
+}
+
+#[test]
+fn forced_cleanup_removes_everything_superfluous() {
+    let tempdir = tempfile::tempdir().unwrap();
+    create_calendars(tempdir.path(), 10);
+
+    let changes = remove_rest(tempdir.path(), &[], CleanupLimit::Forced).unwrap();
+    assert_eq!(changes.len(), 10);
+    assert!(
+        get_existing_files_in(tempdir.path(), "")
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn cleanup_keeps_subfolders() {
+    let tempdir = tempfile::tempdir().unwrap();
+    create_calendars(tempdir.path(), 2);
+    fs::create_dir(tempdir.path().join("events")).unwrap();
+    fs::write(tempdir.path().join("events/BTI1-TI.ics"), "BEGIN:VCAL").unwrap();
+
+    let changes = remove_rest(tempdir.path(), &[], CleanupLimit::Forced).unwrap();
+    assert_eq!(changes.len(), 2);
+    assert!(tempdir.path().join("events/BTI1-TI.ics").exists());
+}
+
+#[test]
+fn write_atomic_replaces_content_without_leftovers() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let path = tempdir.path().join("1-secret.ics");
+
+    write_atomic(&path, b"old").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+
+    write_atomic(&path, b"new").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+    let entries = fs::read_dir(tempdir.path()).unwrap().count();
+    assert_eq!(entries, 1);
+}
+
+#[test]
+fn cleanup_removes_leftover_temp_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stems = create_calendars(tempdir.path(), 2);
+    let leftover = tempdir.path().join(".0-secret.ics.tmp");
+    fs::write(&leftover, "BEGIN:VCAL").unwrap();
+
+    assert_eq!(get_existing_files_in(tempdir.path(), "").unwrap().len(), 2);
+    let changes = remove_rest(tempdir.path(), &stems, CleanupLimit::Calendars(0)).unwrap();
+    assert!(changes.is_empty());
+    assert!(!leftover.exists());
+}
+
+#[test]
+fn cleanup_keeps_artifacts_of_created_calendars() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let stems = create_calendars(tempdir.path(), 2);
+    for stem in &stems {
+        fs::write(tempdir.path().join(format!("{stem}.ics.gz")), "gz").unwrap();
+        fs::write(tempdir.path().join(format!("{stem}.ics.meta.json")), "{}").unwrap();
+    }
+    fs::write(tempdir.path().join("unknown.txt"), "").unwrap();
+
+    let changes = remove_rest(tempdir.path(), &stems[..1], CleanupLimit::Forced).unwrap();
+    let mut removed = changes
+        .into_iter()
+        .map(|change| change.name)
+        .collect::<Vec<_>>();
+    removed.sort();
+    assert_eq!(
+        removed,
+        [
+            "1-secret.ics",
+            "1-secret.ics.gz",
+            "1-secret.ics.meta.json",
+            "unknown.txt"
+        ]
+    );
+    assert!(tempdir.path().join("0-secret.ics.gz").exists());
+}
+
+#[test]
+fn write_artifacts_creates_all_files() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let content = "BEGIN:VCALENDAR\r\nEND:VCALENDAR\r\n";
+    let metadata = Metadata::new(content, &[]);
+    write_artifacts(
+        tempdir.path(),
+        "1-secret",
+        "Peter",
+        &[],
+        &[],
+        content,
+        &metadata,
+    )
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(tempdir.path().join("1-secret.ics")).unwrap(),
+        content
+    );
+    assert!(are_artifacts_complete(tempdir.path(), "1-secret"));
+    let written = Metadata::read(&tempdir.path().join("1-secret.ics.meta.json")).unwrap();
+    assert_eq!(written, metadata);
+
+    remove_artifacts(tempdir.path(), "1-secret").unwrap();
+    assert_eq!(fs::read_dir(tempdir.path()).unwrap().count(), 0);
+}
+
+#[test]
+fn busy_calendar_follows_its_suffix() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let folder = tempdir.path().join(busy_calendar::SUBFOLDER);
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+        .unwrap()
+        .and_hms_opt(8, 15, 0)
+        .unwrap();
+    let events = [SoonToBeIcsEvent {
+        name: "BTI5-VS".to_owned(),
+        start_time,
+        end_time: start_time + chrono::Duration::hours(3),
+        location: "1060".to_owned(),
+        ..SoonToBeIcsEvent::default()
+    }];
+
+    write_busy_calendar(&folder, 1, Some("1-busy"), &events).unwrap();
+    fs::write(folder.join("12-busy.ics"), "BEGIN:VCALENDAR").unwrap();
+    let content = fs::read_to_string(folder.join("1-busy.ics")).unwrap();
+    assert!(content.contains("SUMMARY:Belegt"), "{content}");
+    assert!(!content.contains("BTI5-VS"), "{content}");
+
+    write_busy_calendar(&folder, 1, Some("1-other"), &events).unwrap();
+    assert!(!folder.join("1-busy.ics").exists());
+    assert!(folder.join("1-other.ics").exists());
+
+    write_busy_calendar(&folder, 1, None, &events).unwrap();
+    assert_eq!(get_existing_files_in(&folder, "").unwrap(), ["12-busy.ics"]);
+}
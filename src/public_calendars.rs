@@ -0,0 +1,194 @@
+//! Calendars of single eventfiles without any user, subscribable by everyone: `calendars/events/<eventfile>.ics`
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context as _;
+
+use crate::changestatus::{Changestatus, Changetype};
+use crate::eventfiles_guard::count_events;
+use crate::events;
+use crate::generate_ics::{SoonToBeIcsEvent, generate_ics};
+use crate::output_files::{self, write_atomic};
+
+/// Subfolder of the calendars folder
+pub const SUBFOLDER: &str = "events";
+
+/// Build the calendar of every eventfile and remove the ones of eventfiles which no longer exist.
+pub fn build_all() -> anyhow::Result<Vec<Changestatus>> {
+    build_all_in(
+        &Path::new(events::FOLDER).join("events"),
+        &Path::new(output_files::FOLDER).join(SUBFOLDER),
+    )
+}
+
+fn build_all_in(source: &Path, target: &Path) -> anyhow::Result<Vec<Changestatus>> {
+    let eventfiles = count_events(source).into_keys().collect::<Vec<_>>();
+    let mut changestati = Vec::new();
+
+    for eventfile in &eventfiles {
+        let source_path = source.join(format!("{eventfile}.json"));
+        let target_path = target.join(format!("{eventfile}.ics"));
+        // Comparing the content also rebuilds calendars of unchanged eventfiles when the rendering changed
+        let changetype = match build_one(eventfile, &source_path, &target_path) {
+            Ok(changetype) => changetype,
+            Err(err) => {
+                println!("Failed to build public calendar {eventfile}: {err:#}");
+                Changetype::Failed
+            }
+        };
+        changestati.push(Changestatus {
+            name: eventfile.clone(),
+            changetype,
+        });
+    }
+
+    let mut existing = Vec::new();
+    find_calendars(target, target, &mut existing)
+        .context("failed to read existing public calendars")?;
+    for calendar in existing {
+        if !eventfiles.contains(&calendar) {
+            fs::remove_file(target.join(format!("{calendar}.ics")))
+                .with_context(|| format!("failed to remove public calendar {calendar}"))?;
+            changestati.push(Changestatus {
+                name: calendar,
+                changetype: Changetype::Removed,
+            });
+        }
+    }
+
+    Ok(changestati)
+}
+
+fn build_one(
+    eventfile: &str,
+    source_path: &Path,
+    target_path: &Path,
+) -> anyhow::Result<Changetype> {
+    let mut events = events::read_file(source_path)?
+        .into_iter()
+        .map(SoonToBeIcsEvent::from)
+        .collect::<Vec<_>>();
+    events.sort_by_cached_key(|event| event.start_time);
+    let content = generate_ics(eventfile, &events);
+
+    let changetype = match fs::read_to_string(target_path) {
+        Ok(existing) if existing == content => return Ok(Changetype::Same),
+        Ok(_) => Changetype::Changed,
+        Err(_) => Changetype::Added,
+    };
+    if let Some(folder) = target_path.parent() {
+        fs::create_dir_all(folder).context("failed to create folder")?;
+    }
+    write_atomic(target_path, content.as_bytes()).context("failed to write")?;
+    Ok(changetype)
+}
+
+/// Eventfile names of the existing calendars, relative to the root without the extension
+fn find_calendars(root: &Path, folder: &Path, result: &mut Vec<String>) -> std::io::Result<()> {
+    if !folder.exists() {
+        return Ok(());
+    }
+    for maybe_entry in fs::read_dir(folder)? {
+        let entry = maybe_entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            find_calendars(root, &path, result)?;
+            continue;
+        }
+        let is_hidden = entry.file_name().to_string_lossy().starts_with('.');
+        if is_hidden || path.extension().is_none_or(|extension| extension != "ics") {
+            continue;
+        }
+        if let Some(name) = path
+            .strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.with_extension("").to_str().map(ToOwned::to_owned))
+        {
+            result.push(name);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+const EVENTFILE: &str = r#"[{"name": "BTI1-TI", "location": "1060", "description": "Dozent: HTM", "startTime": "2022-01-13T11:40:00", "endTime": "2022-01-13T12:00:00"}]"#;
+
+#[test]
+fn builds_calendar_per_eventfile() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    fs::create_dir_all(source.join("sub")).unwrap();
+    fs::write(source.join("BTI1-TI.json"), EVENTFILE).unwrap();
+    fs::write(source.join("sub/other.json"), "[]").unwrap();
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert_eq!(changes.len(), 2);
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == Changetype::Added)
+    );
+    let content = fs::read_to_string(target.join("BTI1-TI.ics")).unwrap();
+    assert!(content.contains("SUMMARY:BTI1-TI\r\n"), "{content}");
+    assert!(content.contains("X-WR-CALNAME:@HAWHHCalendarBot (BTI1-TI)\r\n"));
+    assert!(target.join("sub/other.ics").exists());
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == Changetype::Same)
+    );
+}
+
+#[test]
+fn changed_eventfile_is_rebuilt() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    fs::create_dir_all(&source).unwrap();
+    fs::write(source.join("BTI1-TI.json"), "[]").unwrap();
+    build_all_in(&source, &target).unwrap();
+
+    fs::write(source.join("BTI1-TI.json"), EVENTFILE).unwrap();
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert_eq!(changes[0].changetype, Changetype::Changed);
+    let content = fs::read_to_string(target.join("BTI1-TI.ics")).unwrap();
+    assert!(content.contains("SUMMARY:BTI1-TI\r\n"), "{content}");
+}
+
+#[test]
+fn outdated_rendering_is_rebuilt() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    fs::create_dir_all(&source).unwrap();
+    fs::create_dir_all(&target).unwrap();
+    fs::write(source.join("BTI1-TI.json"), EVENTFILE).unwrap();
+    // Newer than the eventfile but rendered by an older version
+    fs::write(target.join("BTI1-TI.ics"), "BEGIN:VCALENDAR").unwrap();
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert_eq!(changes[0].changetype, Changetype::Changed);
+    let content = fs::read_to_string(target.join("BTI1-TI.ics")).unwrap();
+    assert!(content.contains("CONTACT:HTM\r\n"), "{content}");
+}
+
+#[test]
+fn calendars_of_removed_eventfiles_are_removed() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    fs::create_dir_all(&source).unwrap();
+    fs::create_dir_all(target.join("sub")).unwrap();
+    fs::write(target.join("sub/gone.ics"), "BEGIN:VCALENDAR").unwrap();
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].name, "sub/gone");
+    assert_eq!(changes[0].changetype, Changetype::Removed);
+    assert!(!target.join("sub/gone.ics").exists());
+}
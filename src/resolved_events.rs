@@ -0,0 +1,236 @@
+//! Events of a user with their details and changes applied, the common base of every output format.
+
+use std::path::Path;
+
+use crate::apply_changes::apply_change;
+use crate::apply_details::apply_details;
+use crate::eventfiles_guard::count_events;
+use crate::events::{self, EventEntry};
+use crate::generate_ics::SoonToBeIcsEvent;
+use crate::userconfig::{EventDetails, RemovedEvents, Userconfig};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedEvent {
+    pub event: SoonToBeIcsEvent,
+    /// Eventfile (without .json) the event originates from
+    pub eventfile: String,
+    /// A change of the user was applied to the event
+    pub changed: bool,
+    /// The change moved the event into another room
+    pub room_changed: bool,
+}
+
+/// Resolve the events of all eventfiles of the user, sorted by their start.
+///
+/// Eventfiles which can not be read are skipped.
+pub fn resolve_events(config: Userconfig) -> Vec<ResolvedEvent> {
+    let mut result = Vec::new();
+    for (filename, details) in config.events {
+        match events::read(&filename) {
+            Ok(entries) => result.append(&mut resolve_eventfile(
+                &filename,
+                entries,
+                details,
+                config.removed_events,
+            )),
+            Err(_) if filename.contains(char::is_uppercase) => {
+                // TODO remove ignore legacy filenames
+            }
+            Err(err) => println!("skip eventfile {filename:>12}.json: {err:#}"),
+        }
+    }
+    result.sort_by_cached_key(|resolved| resolved.event.start_time);
+    result
+}
+
+/// Every event of every eventfile, without the details or changes of any user
+pub fn all_eventfile_events() -> Vec<ResolvedEvent> {
+    all_eventfile_events_in(&Path::new(events::FOLDER).join("events"))
+}
+
+pub fn all_eventfile_events_in(events_folder: &Path) -> Vec<ResolvedEvent> {
+    let mut result = Vec::new();
+    for eventfile in count_events(events_folder).into_keys() {
+        match events::read_file(&events_folder.join(format!("{eventfile}.json"))) {
+            Ok(entries) => result.extend(entries.into_iter().map(|entry| ResolvedEvent {
+                event: entry.into(),
+                eventfile: eventfile.clone(),
+                changed: false,
+                room_changed: false,
+            })),
+            Err(err) => println!("skip eventfile {eventfile:>12}.json: {err:#}"),
+        }
+    }
+    result.sort_by_cached_key(|resolved| resolved.event.start_time);
+    result
+}
+
+fn resolve_eventfile(
+    eventfile: &str,
+    entries: Vec<EventEntry>,
+    details: EventDetails,
+    removed_events: RemovedEvents,
+) -> Vec<ResolvedEvent> {
+    let mut events = Vec::new();
+    for entry in entries {
+        let mut event = entry.into();
+        apply_details(&mut event, &details);
+        events.push(event);
+    }
+
+    // (changed, room_changed) of each event. Kept in sync with the events the changes are applied to.
+    let mut flags = vec![(false, false); events.len()];
+    for (date, change) in details.changes {
+        if let Some(i) = events.iter().position(|event| event.start_time == date) {
+            if change.remove && removed_events == RemovedEvents::Removed {
+                flags.remove(i);
+            } else {
+                flags[i] = (true, flags[i].1 || change.room.is_some());
+            }
+        }
+        apply_change(&mut events, date, change, removed_events);
+    }
+
+    events
+        .into_iter()
+        .zip(flags)
+        .map(|(event, (changed, room_changed))| ResolvedEvent {
+            event,
+            eventfile: eventfile.to_owned(),
+            changed,
+            room_changed,
+        })
+        .collect()
+}
+
+/// Unchanged event of the eventfile in room 1060, tests adapt the fields they care about
+#[cfg(test)]
+pub fn test_event(
+    eventfile: &str,
+    start_time: chrono::NaiveDateTime,
+    minutes: i64,
+) -> ResolvedEvent {
+    ResolvedEvent {
+        event: SoonToBeIcsEvent {
+            name: eventfile.to_owned(),
+            start_time,
+            end_time: start_time + chrono::Duration::minutes(minutes),
+            location: "1060".to_owned(),
+            ..SoonToBeIcsEvent::default()
+        },
+        eventfile: eventfile.to_owned(),
+        changed: false,
+        room_changed: false,
+    }
+}
+
+#[cfg(test)]
+fn create_entries() -> Vec<EventEntry> {
+    let date = |day: u32| {
+        chrono::NaiveDate::from_ymd_opt(2020, 4, day)
+            .unwrap()
+            .and_hms_opt(8, 15, 0)
+            .unwrap()
+    };
+    [2, 9, 16]
+        .into_iter()
+        .map(|day| EventEntry {
+            name: "BTI5-VSP/01".to_owned(),
+            location: "1060".to_owned(),
+            description: String::new(),
+            start_time: events::EventTime::DateTime(date(day)),
+            end_time: events::EventTime::DateTime(date(day) + chrono::Duration::hours(3)),
+            ..EventEntry::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn create_details() -> EventDetails {
+    use crate::userconfig::Change;
+
+    let date = |day: u32| {
+        chrono::NaiveDate::from_ymd_opt(2020, 4, day)
+            .unwrap()
+            .and_hms_opt(8, 15, 0)
+            .unwrap()
+    };
+    let mut details = EventDetails {
+        alert_minutes_before: Some(10),
+        ..EventDetails::default()
+    };
+    details.changes.insert(
+        date(2),
+        Change {
+            remove: true,
+            starttime: None,
+            endtime: None,
+            namesuffix: None,
+            room: None,
+            tentative: None,
+            transparent: None,
+        },
+    );
+    details.changes.insert(
+        date(9),
+        Change {
+            remove: false,
+            starttime: None,
+            endtime: None,
+            namesuffix: None,
+            room: Some("1061".to_owned()),
+            tentative: None,
+            transparent: None,
+        },
+    );
+    details
+}
+
+#[test]
+fn changes_are_flagged() {
+    let result = resolve_eventfile(
+        "BTI5-VSP",
+        create_entries(),
+        create_details(),
+        RemovedEvents::Cancelled,
+    );
+    assert_eq!(result.len(), 3);
+    assert!(
+        result
+            .iter()
+            .all(|resolved| resolved.eventfile == "BTI5-VSP")
+    );
+    assert!(
+        result
+            .iter()
+            .all(|resolved| resolved.event.alert_minutes_before == Some(10))
+    );
+
+    assert!(result[0].changed);
+    assert!(!result[0].room_changed);
+    assert_eq!(
+        result[0].event.status,
+        crate::generate_ics::EventStatus::Cancelled
+    );
+
+    assert!(result[1].changed);
+    assert!(result[1].room_changed);
+    assert_eq!(result[1].event.location, "1061");
+
+    assert!(!result[2].changed);
+    assert!(!result[2].room_changed);
+}
+
+#[test]
+fn flags_follow_removed_events() {
+    let result = resolve_eventfile(
+        "BTI5-VSP",
+        create_entries(),
+        create_details(),
+        RemovedEvents::Removed,
+    );
+    assert_eq!(result.len(), 2);
+    assert!(result[0].room_changed);
+    assert_eq!(result[0].event.location, "1061");
+    assert!(!result[1].changed);
+}
@@ -0,0 +1,135 @@
+//! Occupancy calendar of every room across all eventfiles: `calendars/rooms/<room>.ics` and an index of the rooms.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context as _;
+
+use crate::changestatus::Changestatus;
+use crate::grouped_calendars::{Group, group_events, write_all_in};
+use crate::location::parse_location;
+use crate::resolved_events::{ResolvedEvent, all_eventfile_events_in};
+use crate::{events, output_files};
+
+/// Subfolder of the calendars folder
+pub const SUBFOLDER: &str = "rooms";
+
+pub fn build_all() -> anyhow::Result<Vec<Changestatus>> {
+    build_all_in(
+        &Path::new(events::FOLDER).join("events"),
+        &Path::new(output_files::FOLDER).join(SUBFOLDER),
+    )
+}
+
+fn build_all_in(source: &Path, target: &Path) -> anyhow::Result<Vec<Changestatus>> {
+    let rooms = collect_rooms(&all_eventfile_events_in(source));
+    write_all_in(target, &rooms).context("failed to write room calendars")
+}
+
+/// Events of every room by the key of the room
+fn collect_rooms(events: &[ResolvedEvent]) -> BTreeMap<String, Group> {
+    group_events(events, |event| {
+        parse_location(&event.location)
+            .rooms
+            .iter()
+            .map(|room| (room.key(), room.name()))
+            .collect()
+    })
+}
+
+#[test]
+fn spellings_of_a_room_share_a_calendar() {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2022, 1, 13)
+        .unwrap()
+        .and_hms_opt(11, 40, 0)
+        .unwrap();
+    let event = |location: &str| {
+        let mut resolved = crate::resolved_events::test_event("BTI1-TI", start_time, 20);
+        location.clone_into(&mut resolved.event.location);
+        resolved
+    };
+    let rooms = collect_rooms(&[event("BT7 R1.060"), event("bt7 1.060 / Online")]);
+    assert_eq!(rooms.keys().collect::<Vec<_>>(), ["bt7-1-060"]);
+    assert_eq!(rooms["bt7-1-060"].name, "BT7 1.060");
+    assert_eq!(rooms["bt7-1-060"].events.len(), 2);
+}
+
+#[cfg(test)]
+const EVENTFILE: &str = r#"[
+    {"name": "BTI1-TI", "location": "1060, 1061", "description": "", "startTime": "2022-01-13T11:40:00", "endTime": "2022-01-13T12:00:00"},
+    {"name": "BTI1-TI", "location": "1060", "description": "", "startTime": "2022-01-14T11:40:00", "endTime": "2022-01-14T12:00:00"}
+]"#;
+
+#[test]
+fn builds_calendar_per_room_with_index() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::write(source.join("BTI1-TI.json"), EVENTFILE).unwrap();
+    // Listed again in another eventfile
+    std::fs::write(source.join("BTI1.json"), EVENTFILE).unwrap();
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert_eq!(changes.len(), 2);
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == crate::changestatus::Changetype::Added)
+    );
+
+    let content = std::fs::read_to_string(target.join("1060.ics")).unwrap();
+    assert_eq!(content.matches("BEGIN:VEVENT").count(), 2, "{content}");
+    assert!(content.contains("X-WR-CALNAME:@HAWHHCalendarBot (1060)\r\n"));
+    let content = std::fs::read_to_string(target.join("1061.ics")).unwrap();
+    assert_eq!(content.matches("BEGIN:VEVENT").count(), 1, "{content}");
+
+    let index: Vec<crate::grouped_calendars::IndexEntry> = serde_json::from_str(
+        &std::fs::read_to_string(target.join(crate::grouped_calendars::INDEX)).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        index,
+        [
+            crate::grouped_calendars::IndexEntry {
+                name: "1060".to_owned(),
+                calendar: "1060.ics".to_owned(),
+                event_count: 2,
+            },
+            crate::grouped_calendars::IndexEntry {
+                name: "1061".to_owned(),
+                calendar: "1061.ics".to_owned(),
+                event_count: 1,
+            },
+        ]
+    );
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert!(
+        changes
+            .iter()
+            .all(|change| change.changetype == crate::changestatus::Changetype::Same)
+    );
+}
+
+#[test]
+fn calendars_of_unused_rooms_are_removed() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let source = tempdir.path().join("source");
+    let target = tempdir.path().join("target");
+    std::fs::create_dir_all(&source).unwrap();
+    std::fs::create_dir_all(&target).unwrap();
+    std::fs::write(target.join("1062.ics"), "BEGIN:VCALENDAR").unwrap();
+
+    let changes = build_all_in(&source, &target).unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!(
+        changes[0].changetype,
+        crate::changestatus::Changetype::Removed
+    );
+    assert!(!target.join("1062.ics").exists());
+    assert_eq!(
+        std::fs::read_to_string(target.join(crate::grouped_calendars::INDEX)).unwrap(),
+        "[]"
+    );
+}
@@ -0,0 +1,154 @@
+//! Layout shared by the timetable renderers.
+
+use chrono::{Datelike as _, NaiveDateTime, Timelike as _};
+
+use crate::generate_ics::days_of;
+use crate::resolved_events::ResolvedEvent;
+
+/// Amount of weekdays shown: Monday to Friday and the weekend only when something happens there
+pub fn shown_days(events: &[&ResolvedEvent]) -> u32 {
+    let has_weekend = events
+        .iter()
+        .any(|resolved| resolved.event.start_time.weekday().number_from_monday() > 5);
+    if has_weekend { 7 } else { 5 }
+}
+
+/// Timed events and all day events. All day events are listed above the hours instead of placed within them.
+pub fn split_all_day<'event>(
+    events: &[&'event ResolvedEvent],
+) -> (Vec<&'event ResolvedEvent>, Vec<&'event ResolvedEvent>) {
+    events.iter().partition(|resolved| !resolved.event.all_day)
+}
+
+/// Days of an all day event like `01.02.` or `01.02. - 04.02.`
+pub fn day_range(resolved: &ResolvedEvent) -> String {
+    let first = resolved.event.start_time.date();
+    let last = days_of(&resolved.event).last().unwrap_or(first);
+    if last > first {
+        format!("{} - {}", first.format("%d.%m."), last.format("%d.%m."))
+    } else {
+        first.format("%d.%m.").to_string()
+    }
+}
+
+/// Full hours covering all events: from the earliest start to the latest end.
+/// Events ending on another day end at midnight.
+pub fn hour_range(events: &[&ResolvedEvent]) -> (u32, u32) {
+    let first_hour = events
+        .iter()
+        .map(|resolved| resolved.event.start_time.hour())
+        .min()
+        .unwrap_or(8);
+    let last_hour = events
+        .iter()
+        .map(|resolved| end_hour(resolved.event.start_time, resolved.event.end_time))
+        .max()
+        .unwrap_or(18)
+        .max(first_hour + 1);
+    (first_hour, last_hour)
+}
+
+fn end_hour(start: NaiveDateTime, end: NaiveDateTime) -> u32 {
+    if end.date() > start.date() {
+        24
+    } else if end.minute() > 0 {
+        end.hour() + 1
+    } else {
+        end.hour()
+    }
+}
+
+/// Minutes since `first_hour` the event starts and ends within the day.
+pub fn minutes_within(resolved: &ResolvedEvent, first_hour: u32, last_hour: u32) -> (u32, u32) {
+    let event = &resolved.event;
+    let start = minutes_of_day(event.start_time).saturating_sub(first_hour * 60);
+    let end = if event.end_time.date() > event.start_time.date() {
+        last_hour * 60
+    } else {
+        minutes_of_day(event.end_time).min(last_hour * 60)
+    };
+    (start, end.saturating_sub(first_hour * 60).max(start))
+}
+
+fn minutes_of_day(date: NaiveDateTime) -> u32 {
+    date.hour() * 60 + date.minute()
+}
+
+/// Lane of each event of a day so overlapping events are placed side by side, and the amount of lanes.
+///
+/// The events have to be sorted by their start.
+pub fn lanes(events: &[&ResolvedEvent]) -> (Vec<u32>, u32) {
+    let mut lane_ends: Vec<NaiveDateTime> = Vec::new();
+    let lanes = events
+        .iter()
+        .map(|resolved| {
+            let event = &resolved.event;
+            let lane = if let Some(lane) = lane_ends.iter().position(|end| *end <= event.start_time)
+            {
+                lane_ends[lane] = event.end_time;
+                lane
+            } else {
+                lane_ends.push(event.end_time);
+                lane_ends.len() - 1
+            };
+            u32::try_from(lane).expect("a day should not have that many lanes")
+        })
+        .collect();
+    let amount = u32::try_from(lane_ends.len())
+        .expect("a day should not have that many lanes")
+        .max(1);
+    (lanes, amount)
+}
+
+#[cfg(test)]
+fn create_event(hour: u32, minute: u32, duration_minutes: i64) -> ResolvedEvent {
+    let start_time = chrono::NaiveDate::from_ymd_opt(2020, 4, 2)
+        .unwrap()
+        .and_hms_opt(hour, minute, 0)
+        .unwrap();
+    crate::resolved_events::test_event("BTI5-VS", start_time, duration_minutes)
+}
+
+#[test]
+fn hour_range_examples() {
+    assert_eq!(hour_range(&[]), (8, 18));
+    let early = create_event(8, 15, 180);
+    let late = create_event(14, 0, 120);
+    assert_eq!(hour_range(&[&early, &late]), (8, 16));
+    let over_midnight = create_event(22, 0, 180);
+    assert_eq!(hour_range(&[&early, &over_midnight]), (8, 24));
+}
+
+#[test]
+fn minutes_within_examples() {
+    let event = create_event(8, 15, 180);
+    assert_eq!(minutes_within(&event, 8, 18), (15, 195));
+    let over_midnight = create_event(22, 0, 180);
+    assert_eq!(minutes_within(&over_midnight, 8, 24), (840, 960));
+}
+
+#[test]
+fn lanes_examples() {
+    let first = create_event(8, 0, 120);
+    let overlapping = create_event(9, 0, 120);
+    let afterwards = create_event(10, 0, 60);
+    assert_eq!(lanes(&[]), (vec![], 1));
+    assert_eq!(lanes(&[&first, &afterwards]), (vec![0, 0], 1));
+    assert_eq!(
+        lanes(&[&first, &overlapping, &afterwards]),
+        (vec![0, 1, 0], 2)
+    );
+}
+
+#[test]
+fn all_day_events_are_split() {
+    let timed = create_event(8, 0, 120);
+    let mut all_day = create_event(0, 0, 3 * 24 * 60);
+    all_day.event.all_day = true;
+    let (timed_events, all_day_events) = split_all_day(&[&timed, &all_day]);
+    assert_eq!(timed_events, [&timed]);
+    assert_eq!(all_day_events, [&all_day]);
+    assert_eq!(day_range(&all_day), "02.04. - 04.04.");
+    all_day.event.end_time = all_day.event.start_time;
+    assert_eq!(day_range(&all_day), "02.04.");
+}
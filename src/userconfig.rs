@@ -33,6 +33,12 @@ pub struct EventDetails {
     pub changes: HashMap<NaiveDateTime, Change>,
     #[serde(default)]
     pub notes: Option<String>,
+    /// Every event is optional like voluntary exercises
+    #[serde(default)]
+    pub tentative: bool,
+    /// Every event is shown as free time in free/busy
+    #[serde(default)]
+    pub transparent: bool,
 }
 
 #[derive(Debug, serde::Deserialize)]
@@ -45,6 +51,10 @@ pub struct Userconfig {
 
     #[serde(default)]
     pub removed_events: RemovedEvents,
+
+    /// Secret suffix of the optional busy-only calendar to share the availability
+    #[serde(default)]
+    pub busy_calendarfile_suffix: Option<String>,
 }
 
 #[derive(Debug, PartialEq, Eq, serde::Deserialize)]
@@ -60,6 +70,13 @@ pub struct Change {
 
     pub namesuffix: Option<String>,
     pub room: Option<String>,
+
+    /// Override of the subscription: optional event (`TENTATIVE`)
+    #[serde(default)]
+    pub tentative: Option<bool>,
+    /// Override of the subscription: shown as free time (`TRANSPARENT`)
+    #[serde(default)]
+    pub transparent: Option<bool>,
 }
 
 #[test]
@@ -90,6 +107,7 @@ fn can_deserialize_minimal_userconfig() -> Result<(), serde_json::Error> {
     assert_eq!(test.calendarfile_suffix, "123qwe");
     assert_eq!(test.events.len(), 0);
     assert_eq!(test.removed_events, RemovedEvents::Cancelled);
+    assert_eq!(test.busy_calendarfile_suffix, None);
 
     Ok(())
 }
@@ -110,6 +128,18 @@ fn can_deserialize_userconfig_with_event_map() -> Result<(), serde_json::Error>
     Ok(())
 }
 
+#[test]
+fn can_deserialize_userconfig_with_busy_calendar() -> Result<(), serde_json::Error> {
+    let test: Userconfig = serde_json::from_str(
+        r#"{"calendarfileSuffix": "123qwe", "events": {}, "busyCalendarfileSuffix": "456asd"}"#,
+    )?;
+
+    assert_eq!(test.calendarfile_suffix, "123qwe");
+    assert_eq!(test.busy_calendarfile_suffix.as_deref(), Some("456asd"));
+
+    Ok(())
+}
+
 #[cfg(test)]
 #[track_caller]
 fn case_change(json: &str, expected: &Change) {
@@ -141,6 +171,8 @@ fn can_deserialize_minimal_change() {
             endtime: None,
             namesuffix: None,
             room: None,
+            tentative: None,
+            transparent: None,
         },
     );
 }
@@ -155,6 +187,8 @@ fn can_deserialize_change_remove() {
             endtime: None,
             namesuffix: None,
             room: None,
+            tentative: None,
+            transparent: None,
         },
     );
 }
@@ -169,6 +203,38 @@ fn can_deserialize_change_endtime() {
             endtime: Some(NaiveTime::from_hms_opt(23, 42, 0).unwrap()),
             namesuffix: None,
             room: None,
+            tentative: None,
+            transparent: None,
+        },
+    );
+}
+
+#[test]
+fn can_deserialize_change_tentative() {
+    case_change(
+        r#"{"tentative": true, "transparent": false}"#,
+        &Change {
+            remove: false,
+            starttime: None,
+            endtime: None,
+            namesuffix: None,
+            room: None,
+            tentative: Some(true),
+            transparent: Some(false),
         },
     );
 }
+
+#[test]
+fn can_deserialize_tentative_event_details() -> Result<(), serde_json::Error> {
+    let test: Userconfig = serde_json::from_str(
+        r#"{"calendarfileSuffix": "123qwe", "events": {"BTI1-TI": {}, "BTI1-TI-Tutorium": {"tentative": true, "transparent": true}}}"#,
+    )?;
+
+    assert!(!test.events["BTI1-TI"].tentative);
+    assert!(!test.events["BTI1-TI"].transparent);
+    assert!(test.events["BTI1-TI-Tutorium"].tentative);
+    assert!(test.events["BTI1-TI-Tutorium"].transparent);
+
+    Ok(())
+}
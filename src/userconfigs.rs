@@ -7,31 +7,63 @@ use crate::userconfig::UserconfigFile;
 
 pub const FOLDER: &str = "userconfig";
 
+pub struct Loaded {
+    pub successful: Vec<UserconfigFile>,
+    /// Chat ids with an unreadable userconfig. Their calendars should stay untouched.
+    pub failed: Vec<i64>,
+}
+
 pub fn load_specific(filename: &str) -> anyhow::Result<UserconfigFile> {
-    let path = Path::new(FOLDER).join(filename);
+    load_specific_in(Path::new(FOLDER), filename)
+}
+
+/// Userconfig of the chat like `1337.json`
+pub fn load_chat(chat_id: i64) -> anyhow::Result<UserconfigFile> {
+    load_specific(&format!("{chat_id}.json"))
+        .with_context(|| format!("failed to load userconfig of {chat_id}"))
+}
+
+fn load_specific_in(folder: &Path, filename: &str) -> anyhow::Result<UserconfigFile> {
+    let path = folder.join(filename);
     let content = fs::read_to_string(path).context("failed to read")?;
     let parsed: UserconfigFile = serde_json::from_str(&content).context("failed to parse")?;
     Ok(parsed)
 }
 
-pub fn load_all() -> Vec<UserconfigFile> {
+pub fn load_all() -> Loaded {
+    load_all_in(Path::new(FOLDER))
+}
+
+fn load_all_in(folder: &Path) -> Loaded {
     let mut successful: Vec<UserconfigFile> = Vec::new();
+    let mut failed: Vec<i64> = Vec::new();
 
-    let existing_files = get_existing_files().expect("should be able to read userconfig directory");
+    let existing_files =
+        get_existing_files(folder).expect("should be able to read userconfig directory");
 
     for filename in existing_files {
-        match load_specific(&filename) {
+        match load_specific_in(folder, &filename) {
             Ok(content) => successful.push(content),
-            Err(err) => println!("skip userconfig {filename:>16}: {err:#}"),
+            Err(err) => {
+                println!("skip userconfig {filename:>16}: {err:#}");
+                if let Some(chat_id) = chat_id_of_filename(&filename) {
+                    failed.push(chat_id);
+                }
+            }
         }
     }
 
-    successful
+    Loaded { successful, failed }
+}
+
+/// Userconfigs are named after their chat id like `1337.json`
+fn chat_id_of_filename(filename: &str) -> Option<i64> {
+    filename.strip_suffix(".json")?.parse().ok()
 }
 
-fn get_existing_files() -> std::io::Result<Vec<String>> {
+fn get_existing_files(folder: &Path) -> std::io::Result<Vec<String>> {
     let mut list: Vec<String> = Vec::new();
-    for maybe_entry in fs::read_dir(FOLDER)? {
+    for maybe_entry in fs::read_dir(folder)? {
         let filename = maybe_entry?
             .file_name()
             .into_string()
@@ -45,3 +77,21 @@ fn get_existing_files() -> std::io::Result<Vec<String>> {
 
     Ok(list)
 }
+
+#[test]
+fn unreadable_userconfigs_are_tracked_as_failed() {
+    let tempdir = tempfile::tempdir().unwrap();
+    let folder = tempdir.path();
+    fs::write(
+        folder.join("42.json"),
+        r#"{"chat": {"id": 42, "first_name": "Peter"}, "config": {"calendarfileSuffix": "123qwe", "events": {}}}"#,
+    )
+    .unwrap();
+    fs::write(folder.join("666.json"), r#"{"chat": {"id": 666"#).unwrap();
+    fs::write(folder.join("whatever.json"), "").unwrap();
+
+    let loaded = load_all_in(folder);
+    assert_eq!(loaded.successful.len(), 1);
+    assert_eq!(loaded.successful[0].chat.id, 42);
+    assert_eq!(loaded.failed, [666]);
+}